@@ -143,7 +143,7 @@ impl<T> FileTree<T> {
         let previous_path = env::current_dir()?;
         debug_assert!(path.is_absolute());
         env::set_current_dir(path)?;
-        let result = Self::__collect_from_directory(&Path::new("."), follow_symlinks);
+        let result = Self::__collect_from_directory(Path::new("."), follow_symlinks);
         env::set_current_dir(previous_path)?;
         result
     }
@@ -225,7 +225,7 @@ impl<T> FileTree<T> {
         let previous_path = env::current_dir()?;
         debug_assert!(path.is_absolute());
         env::set_current_dir(path)?;
-        let result = Self::__from_path(&Path::new("."), follow_symlinks);
+        let result = Self::__from_path(Path::new("."), follow_symlinks);
         env::set_current_dir(previous_path)?;
         result
     }
@@ -399,14 +399,14 @@ impl<T> FileTree<T> {
     }
 
     /// Iterator of all `FileTree`s in the structure
-    pub fn files(&self) -> FilesIter<T> {
+    pub fn files(&self) -> FilesIter<'_, T> {
         FilesIter::new(self)
     }
 
     /// Shorthand for `self.files().paths()`, see link to [`.paths()`] method
     ///
     /// [`.paths()`]: super::iter::FilesIter::paths
-    pub fn paths(&self) -> PathsIter<T> {
+    pub fn paths(&self) -> PathsIter<'_, T> {
         self.files().paths()
     }
 
@@ -58,32 +58,6 @@ impl<'a, T> FilesIter<'a, T> {
         PathsIter::new(self)
     }
 
-    /// Retrieve files before the directories when possible
-    ///
-    /// If true, when reading files inside a directory, put it in the start or
-    /// end of the Deque in a way that is deterministic
-    ///
-    /// Example:
-    /// ```txt
-    /// //// This was commented out because we don't have nice macros to make this example convenient
-    /// // let mut it = root.files().files_before_directories(true);
-    /// // assert_eq!(it.next(), Some(refs[0])); // .config/
-    /// // assert_eq!(it.next(), Some(refs[8])); // .config/outerfile1
-    /// // assert_eq!(it.next(), Some(refs[9])); // .config/outerfile2
-    /// // assert_eq!(it.next(), Some(refs[1])); // .config/i3/
-    /// // assert_eq!(it.next(), Some(refs[2])); // .config/i3/file1
-    /// // assert_eq!(it.next(), Some(refs[3])); // .config/i3/file2
-    /// // assert_eq!(it.next(), Some(refs[7])); // .config/i3/file3
-    /// // assert_eq!(it.next(), Some(refs[4])); // .config/i3/dir/
-    /// // assert_eq!(it.next(), Some(refs[5])); // .config/i3/dir/innerfile1
-    /// // assert_eq!(it.next(), Some(refs[6])); // .config/i3/dir/innerfile2
-    /// ```
-    ///
-    /// Se above that when we enter a directory, first we go into the files in
-    /// that directory, and then in the files of that directory, this guarantees
-    /// that when we see depth of `X`, we can only see depth of `X` or `X + 1`
-    /// for the next call.
-
     // Removed option, I don't think it'll come back
     // pub fn files_before_directories(mut self, arg: bool) -> Self {
     //     self.files_before_directories = arg;
@@ -163,7 +137,7 @@ impl<'a, T> Iterator for FilesIter<'a, T> {
             return self.next();
         }
 
-        Some(&file)
+        Some(file)
     }
 }
 
@@ -64,7 +64,6 @@ macro_rules! dir {
 /// //     "file3"
 /// // ]);
 /// ```
-
 #[macro_export]
 macro_rules! tree {
     ($($any:tt)*) => {{
@@ -15,17 +15,18 @@ use crate::error::*;
 /// - If `Io::Error` from `fs::read_link(path)`
 pub fn symlink_target<P: AsRef<Path>>(path: P) -> FtResult<PathBuf> {
     let path = path.as_ref();
-    if !path.exists() {
+    // Don't follow it, broken symlinks still have a target
+    if fs::symlink_metadata(path).is_err() {
         return Err(FtError::NotFoundError(path.to_path_buf()));
         // "while trying to read symlink target path",
     }
 
-    if !FileTypeEnum::from_path(path)?.is_symlink() {
+    if !FileTypeEnum::from_symlink_path(path)?.is_symlink() {
         return Err(FtError::NotASymlinkError(path.to_path_buf()));
         // "while trying to read symlink target path",
     }
 
-    let target = fs::read_link(&path)?;
+    let target = fs::read_link(path)?;
 
     // .map_err(|source| { FtError::new(
     //         FtErrorKind::ReadError(source),
@@ -1,25 +1,33 @@
 use std::os::raw::c_int;
 
 bitflags! {
-    /// Classes of permissions are: Owner, Group, Other, or a combination of them
+    /// Classes of permissions are: Owner, Group, Other, or a combination of them.
     pub struct Classes: c_int {
+        /// No class.
         const EMPTY = 0b000;
+        /// The owner of the file.
         const OWNER = 0b100;
+        /// The group of the file.
         const GROUP = 0b010;
+        /// Everyone else.
         const OTHER = 0b001;
+        /// Owner, group and other.
         const ALL_CLASSES = 0b111;
     }
 }
 
 impl Classes {
+    /// If the owner class is set.
     pub fn is_owner_set(&self) -> bool {
         self.bits() & Self::OWNER.bits() != 0
     }
 
+    /// If the group class is set.
     pub fn is_group_set(&self) -> bool {
         self.bits() & Self::GROUP.bits() != 0
     }
 
+    /// If the other class is set.
     pub fn is_other_set(&self) -> bool {
         self.bits() & Self::OTHER.bits() != 0
     }
@@ -7,7 +7,7 @@
 //!
 //! Also, be aware of [`TOCTOU race conditions`], permissions can change since the last checks.
 
-use std::{io, os::raw::c_int, path::Path};
+use std::{fs, io, os::raw::c_int, path::Path};
 
 /// Check if current process has permission to remove file.
 ///
@@ -15,8 +15,10 @@ use std::{io, os::raw::c_int, path::Path};
 ///
 /// Returns `false` if there's no parent directory (can't delete the system's root).
 ///
+/// Symlinks are not followed, the parent of the link itself is checked.
+///
 /// # Errors
-/// - If [`Path::canonicalize`] fails.
+/// - If the path doesn't exist, or [`Path::canonicalize`] fails for its parent.
 /// - Same as [`access_syscall`].
 ///
 /// # Examples
@@ -38,13 +40,22 @@ use std::{io, os::raw::c_int, path::Path};
 ///
 /// [`Path::canonicalize`]: std::path::Path::canonicalize
 pub fn is_removable(path: impl AsRef<Path>) -> io::Result<bool> {
-    let path = path.as_ref().canonicalize()?;
+    let path = path.as_ref();
+    fs::symlink_metadata(path)?;
+    // Only the parent is resolved, removing a symlink doesn't touch what it points to
+    let path = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if parent.as_os_str().is_empty() => {
+            Path::new(".").canonicalize()?.join(name)
+        },
+        (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+        _ => path.canonicalize()?,
+    };
     let parent = match path.parent() {
         // Cannot delete '/' (root directory)
         None => return Ok(false),
         Some(parent) => parent,
     };
-    access_syscall(&parent, libc::W_OK)
+    access_syscall(parent, libc::W_OK)
 }
 
 /// Check if current process has permission to create file.
@@ -79,7 +90,7 @@ pub fn is_creatable(path: impl AsRef<Path>) -> io::Result<bool> {
         None => return Ok(false),
         Some(parent) => parent,
     };
-    access_syscall(&parent, libc::W_OK)
+    access_syscall(parent, libc::W_OK)
 }
 
 /// Check if current process has permission to read.
@@ -104,7 +115,7 @@ pub fn is_creatable(path: impl AsRef<Path>) -> io::Result<bool> {
 /// }
 /// ```
 pub fn is_readable(path: impl AsRef<Path>) -> io::Result<bool> {
-    access_syscall(&path.as_ref(), libc::R_OK)
+    access_syscall(path.as_ref(), libc::R_OK)
 }
 
 /// Check if current process has permission to write.
@@ -129,7 +140,7 @@ pub fn is_readable(path: impl AsRef<Path>) -> io::Result<bool> {
 /// }
 /// ```
 pub fn is_writable(path: impl AsRef<Path>) -> io::Result<bool> {
-    access_syscall(&path.as_ref(), libc::W_OK)
+    access_syscall(path.as_ref(), libc::W_OK)
 }
 
 /// Check if current process has permission to execute.
@@ -158,7 +169,7 @@ pub fn is_writable(path: impl AsRef<Path>) -> io::Result<bool> {
 /// }
 /// ```
 pub fn is_executable(path: impl AsRef<Path>) -> io::Result<bool> {
-    access_syscall(&path.as_ref(), libc::X_OK)
+    access_syscall(path.as_ref(), libc::X_OK)
 }
 
 /// Safe wrapper to the `libc::access` syscall.
@@ -33,17 +33,17 @@
 //! }
 //! ```
 //!
-//! # Future
-//! For this crate I plan on adding a nicer and convenient `rwx` bitmask interface, in the 0.4
-//! version.
-//!
-//! If you're interested in this implemented, open an issue and I'll try to complete it sooner.
-//!
-//! I haven't finished it yet beucase I've never needed this functionality, the existing functions
-//! were enough for my use cases.
+//! # Permission bits
+//! For `unix` modes, [`PermissionBits`] holds the `rwx` [`ModeBits`] of each of the [`Classes`]
+//! _(Owner, Group, Other)_:
+//! ```
+//! use permissions::{prelude::*, PermissionBits};
 //!
-//! (Part of the code for `rwx` and `(Owner | Group | Other)` permissions bitflags are already
-//! available at the project's repository)
+//! let bits = PermissionBits::empty().set(OWNER, READ | WRITE, true);
+//! assert_eq!(bits.bits(), 0o600);
+//! assert_eq!(format!("{:o}", bits), "600");
+//! assert_eq!(PermissionBits::from_bits_checked(0o1600), None);
+//! ```
 //!
 //! # Helping/Contributing:
 //! It's easy to contribute to this crate, here are some options:
@@ -56,4 +56,16 @@
 #![warn(missing_docs)]
 
 pub mod functions;
+pub mod prelude;
+
+#[macro_use]
+extern crate bitflags;
+
+mod classes;
+mod mode_bits;
+mod permission_bits;
+
+pub use classes::Classes;
 pub use functions::*;
+pub use mode_bits::ModeBits;
+pub use permission_bits::PermissionBits;
@@ -3,10 +3,15 @@ use std::os::raw::c_int;
 bitflags! {
     /// Bits for combination of `rwx` modes (`Read`, `Write` and `Execute`).
     pub struct ModeBits: c_int {
+        /// No permission.
         const NULL    = 0b000;
+        /// Permission to read.
         const READ    = libc::R_OK;
+        /// Permission to write.
         const WRITE   = libc::W_OK;
+        /// Permission to execute (or search, for directories).
         const EXECUTE = libc::X_OK;
+        /// Read, write and execute.
         const ALL_BITS = libc::R_OK | libc::W_OK | libc::X_OK;
     }
 }
@@ -18,14 +23,17 @@ impl Default for ModeBits {
 }
 
 impl ModeBits {
+    /// If the read bit is set.
     pub fn is_read_set(&self) -> bool {
         self.bits() & Self::READ.bits() != 0
     }
 
+    /// If the write bit is set.
     pub fn is_write_set(&self) -> bool {
         self.bits() & Self::WRITE.bits() != 0
     }
 
+    /// If the execute bit is set.
     pub fn is_execute_set(&self) -> bool {
         self.bits() & Self::EXECUTE.bits() != 0
     }
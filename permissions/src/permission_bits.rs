@@ -1,25 +1,25 @@
-use crate::{Classes, ModeBits};
-
 use std::{
-    fs,
+    fmt, fs,
     os::{raw::c_int, unix::fs::PermissionsExt},
 };
 
+use crate::{Classes, ModeBits};
+
+/// The `rwx` bits of each class, as in the `0o777` part of a `unix` file mode.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
 pub struct PermissionBits {
+    /// Bits of the owner of the file.
     pub owner_bits: ModeBits,
+    /// Bits of the group of the file.
     pub group_bits: ModeBits,
+    /// Bits of everyone else.
     pub other_bits: ModeBits,
 }
 
 impl PermissionBits {
     /// Function to build new PermissionBits with each field.
     pub fn new(owner_bits: ModeBits, group_bits: ModeBits, other_bits: ModeBits) -> Self {
-        PermissionBits {
-            owner_bits,
-            group_bits,
-            other_bits,
-        }
+        PermissionBits { owner_bits, group_bits, other_bits }
     }
 
     /// Constructs from bits with checks.
@@ -49,6 +49,7 @@ impl PermissionBits {
         PermissionBits::new(owner, group, other)
     }
 
+    /// No bits set, as in `0o000`.
     pub const fn empty() -> Self {
         PermissionBits {
             owner_bits: ModeBits::empty(),
@@ -57,6 +58,7 @@ impl PermissionBits {
         }
     }
 
+    /// Every bit set, as in `0o777`.
     pub const fn all() -> Self {
         PermissionBits {
             owner_bits: ModeBits::all(),
@@ -65,8 +67,7 @@ impl PermissionBits {
         }
     }
 
-    /// improve this help lalalalalalala
-    /// Prepare a 0o777 style from 3 0b111 style lalala
+    /// The bits in `0o777` style, from the `0b111` style bits of each class.
     pub fn bits(&self) -> c_int {
         let mut result = 0;
         result |= self.owner_bits.bits() << 3 << 3;
@@ -75,26 +76,31 @@ impl PermissionBits {
         result
     }
 
+    /// If no bit is set.
     pub const fn is_empty(&self) -> bool {
         self.owner_bits.is_empty() && self.group_bits.is_empty() && self.other_bits.is_empty()
     }
 
+    /// If every bit is set.
     pub const fn is_all(&self) -> bool {
         self.owner_bits.is_all() && self.group_bits.is_all() && self.other_bits.is_all()
     }
 
+    /// If every class intersects with the same class of `other`.
     pub fn intersects(&self, other: PermissionBits) -> bool {
         self.owner_bits.intersects(other.owner_bits)
             && self.group_bits.intersects(other.group_bits)
             && self.other_bits.intersects(other.other_bits)
     }
 
+    /// If every class contains the same class of `other`.
     pub fn contains(&self, other: PermissionBits) -> bool {
         self.owner_bits.contains(other.owner_bits)
             && self.group_bits.contains(other.group_bits)
             && self.other_bits.contains(other.other_bits)
     }
 
+    /// Set `bits` for each of `classes`.
     pub fn insert(&mut self, classes: Classes, bits: ModeBits) -> Self {
         if classes.is_owner_set() {
             self.owner_bits.insert(bits);
@@ -108,6 +114,7 @@ impl PermissionBits {
         *self
     }
 
+    /// Unset `bits` for each of `classes`.
     pub fn remove(&mut self, classes: Classes, bits: ModeBits) -> Self {
         if classes.is_owner_set() {
             self.owner_bits.remove(bits);
@@ -121,6 +128,7 @@ impl PermissionBits {
         *self
     }
 
+    /// Set or unset `bits` for each of `classes`, according to `value`.
     pub fn set(&mut self, classes: Classes, bits: ModeBits, value: bool) -> Self {
         if classes.is_owner_set() {
             self.owner_bits.set(bits, value);
@@ -137,8 +145,14 @@ impl PermissionBits {
 
 impl From<fs::Permissions> for PermissionBits {
     fn from(fs_permissions: fs::Permissions) -> Self {
-        // Is this cast safe?
-        PermissionBits::from_bits(fs_permissions.mode() as c_int)
+        // Other bits (file type, setuid, sticky...) are left out
+        PermissionBits::from_bits(fs_permissions.mode() as c_int & 0o777)
+    }
+}
+
+impl fmt::Octal for PermissionBits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Octal::fmt(&self.bits(), f)
     }
 }
 
@@ -1,3 +1,6 @@
+//! Constants of [`ModeBits`] and [`Classes`], to build [`PermissionBits`](crate::PermissionBits)
+//! concisely.
+
 use crate::{Classes, ModeBits};
 
 /// ModeBits::READ
@@ -17,5 +20,3 @@ pub const OWNER: Classes = Classes::OWNER;
 pub const GROUP: Classes = Classes::GROUP;
 /// Classes::OTHER
 pub const OTHER: Classes = Classes::OTHER;
-
-use OTHER as Other;
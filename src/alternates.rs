@@ -0,0 +1,298 @@
+//! Alternates, several sources for one target path, each with conditions on the machine.
+//!
+//! Conditions go at the end of the source name, after `##`, separated by commas, as in
+//! `alacritty.toml##os.linux` or `.Xresources##host.laptop,tag.work`. Variants of a file are the
+//! entries of a group in the same directory whose names are the same before `##`, the best match
+//! among them is linked at the name without the conditions, the others are left out.
+
+use std::{
+    ffi::CStr,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use tsml::{FileTree, GroupsMap};
+
+use crate::diff::has_active_tags;
+
+/// Separates the name of a source from its conditions.
+pub const SEPARATOR: &str = "##";
+
+/// What the conditions of alternates are checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Facts {
+    /// As in [`std::env::consts::OS`], like `linux` or `macos`.
+    pub os: String,
+    /// As in [`std::env::consts::ARCH`], like `x86_64` or `aarch64`.
+    pub arch: String,
+    /// Name of the machine, empty if unknown.
+    pub host: String,
+}
+
+impl Facts {
+    /// Facts of the machine we're running on.
+    pub fn detect() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            host: hostname().unwrap_or_default(),
+        }
+    }
+}
+
+fn hostname() -> Option<String> {
+    let mut buffer = [0 as libc::c_char; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) } != 0 {
+        return None;
+    }
+    // Truncated names might not be terminated
+    *buffer.last_mut()? = 0;
+    let name = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    name.to_str().ok().map(str::to_string)
+}
+
+/// A condition of a variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Os(String),
+    Arch(String),
+    Host(String),
+    /// One of the active tags.
+    Tag(String),
+    /// Matches when no other variant does.
+    Default,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Option<Self> {
+        if text == "default" {
+            return Some(Self::Default);
+        }
+        let (key, value) = text.split_once('.')?;
+        let value = value.to_string();
+        match key {
+            _ if value.is_empty() => None,
+            "os" => Some(Self::Os(value)),
+            "arch" => Some(Self::Arch(value)),
+            "host" => Some(Self::Host(value)),
+            "tag" => Some(Self::Tag(value)),
+            _ => None,
+        }
+    }
+
+    fn holds(&self, facts: &Facts, active_tags: &[String]) -> bool {
+        match self {
+            Self::Os(os) => *os == facts.os,
+            Self::Arch(arch) => *arch == facts.arch,
+            Self::Host(host) => *host == facts.host,
+            Self::Tag(tag) => active_tags.contains(tag),
+            Self::Default => true,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Os(os) => write!(f, "os is {}", os),
+            Self::Arch(arch) => write!(f, "arch is {}", arch),
+            Self::Host(host) => write!(f, "host is {}", host),
+            Self::Tag(tag) => write!(f, "tag {} is active", tag),
+            Self::Default => write!(f, "no other variant matches"),
+        }
+    }
+}
+
+/// Split the name of a variant into the name of the target and the text of its conditions,
+/// `None` if it isn't a variant, names with anything but valid conditions after `##` are plain
+/// files.
+pub fn split_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(SEPARATOR)
+        .filter(|(base, conditions)| !base.is_empty() && parse_conditions(conditions).is_ok())
+}
+
+/// The conditions in `text`, as split by [`split_name`], fails with the first invalid one.
+pub fn parse_conditions(text: &str) -> Result<Vec<Condition>, String> {
+    text.split(',')
+        .map(|condition| Condition::parse(condition.trim()).ok_or_else(|| condition.to_string()))
+        .collect()
+}
+
+/// `path` without the conditions in its name, if it's a variant.
+pub fn target_path(path: &Path) -> PathBuf {
+    let base = path.file_name().and_then(|name| name.to_str()).and_then(split_name);
+    match base {
+        Some((base, _)) => path.with_file_name(base),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Variants of a target, and which of them is linked.
+#[derive(Debug, Clone)]
+pub struct Alternate<'a> {
+    pub group: &'a str,
+    /// Path of the variants inside of the group folder, without the conditions.
+    pub path: PathBuf,
+    /// Sorted by name.
+    pub variants: Vec<&'a FileTree>,
+    /// The variant that is linked, `None` if none matches.
+    pub chosen: Option<&'a FileTree>,
+    /// Why it was chosen, the conditions that hold, or why none was.
+    pub reason: String,
+}
+
+impl Alternate<'_> {
+    /// If `file` is one of the variants, but not the chosen one.
+    pub fn leaves_out(&self, file: &FileTree) -> bool {
+        let is_file = |variant: &&FileTree| std::ptr::eq(*variant, file);
+        self.variants.iter().any(is_file) && !self.chosen.iter().any(is_file)
+    }
+}
+
+// How specific the conditions of a variant are, the amount of each kind, from the most specific
+// kind (`host`) to the least (`tag`), compared in that order, so kinds don't add up to a more
+// specific one
+fn specificity(conditions: &[Condition]) -> [usize; 4] {
+    let mut amounts = [0; 4];
+    for condition in conditions {
+        match condition {
+            Condition::Host(_) => amounts[0] += 1,
+            Condition::Os(_) => amounts[1] += 1,
+            Condition::Arch(_) => amounts[2] += 1,
+            Condition::Tag(_) => amounts[3] += 1,
+            Condition::Default => {},
+        }
+    }
+    amounts
+}
+
+// Pick the variant with the most specific conditions that all hold, the first one by name breaks
+// ties, along with the reason
+fn choose<'a>(
+    variants: &[&'a FileTree],
+    facts: &Facts,
+    active_tags: &[String],
+) -> (Option<&'a FileTree>, String) {
+    let mut best: Option<([usize; 4], &FileTree, Vec<Condition>)> = None;
+    for variant in variants.iter() {
+        let name = variant.path().file_name().unwrap_or_default().to_string_lossy();
+        // Safe unwraps, only variants are grouped, and their conditions are valid
+        let (_, conditions) = split_name(&name).unwrap();
+        let conditions = parse_conditions(conditions).unwrap();
+        if !conditions.iter().all(|condition| condition.holds(facts, active_tags)) {
+            continue;
+        }
+        let specificity = specificity(&conditions);
+        if best.as_ref().is_none_or(|(best_specificity, ..)| specificity > *best_specificity) {
+            best = Some((specificity, variant, conditions));
+        }
+    }
+
+    match best {
+        Some((_, variant, conditions)) => {
+            let reasons: Vec<String> = conditions.iter().map(Condition::to_string).collect();
+            (Some(variant), reasons.join(", "))
+        },
+        None => (None, "no variant matches".to_string()),
+    }
+}
+
+/// Alternates of the entries of `map` with active tags, by group and path.
+pub fn alternates<'a>(
+    map: &'a GroupsMap,
+    active_tags: &[String],
+    facts: &Facts,
+) -> Vec<Alternate<'a>> {
+    let mut alternates = vec![];
+    for (group, trees) in map.iter() {
+        let files = trees.iter().flat_map(|tree| tree.files().skip_dirs(true));
+        let mut variants: Vec<(PathBuf, &FileTree)> = files
+            .filter(|file| has_active_tags(file, active_tags))
+            .filter(|file| target_path(file.path()) != *file.path())
+            .map(|file| (target_path(file.path()), file))
+            .collect();
+        variants.sort_by(|(a, a_file), (b, b_file)| (a, a_file.path()).cmp(&(b, b_file.path())));
+
+        for chunk in variants.chunk_by(|(a, _), (b, _)| a == b) {
+            let variants: Vec<&FileTree> = chunk.iter().map(|(_, file)| *file).collect();
+            let (chosen, reason) = choose(&variants, facts, active_tags);
+            let path = chunk[0].0.clone();
+            alternates.push(Alternate { group, path, variants, chosen, reason });
+        }
+    }
+    alternates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts() -> Facts {
+        Facts { os: "linux".to_string(), arch: "x86_64".to_string(), host: "laptop".to_string() }
+    }
+
+    fn chosen(names: &[&str], active_tags: &[&str]) -> (Option<String>, String) {
+        let files: Vec<FileTree> = names.iter().map(FileTree::new_regular).collect();
+        let variants: Vec<&FileTree> = files.iter().collect();
+        let active_tags: Vec<String> = active_tags.iter().map(|tag| tag.to_string()).collect();
+        let (chosen, reason) = choose(&variants, &facts(), &active_tags);
+        (chosen.map(|file| file.path().to_string_lossy().into_owned()), reason)
+    }
+
+    #[test]
+    fn names_of_variants() {
+        assert_eq!(split_name("alacritty.toml##os.linux"), Some(("alacritty.toml", "os.linux")));
+        assert_eq!(split_name("alacritty.toml"), None);
+        assert_eq!(split_name("##os.linux"), None);
+        // Not made of conditions, so not a variant
+        assert_eq!(split_name("notes##draft"), None);
+        assert_eq!(split_name("x##os.linux,color.red"), None);
+        assert_eq!(target_path(Path::new("a/notes##draft")), Path::new("a/notes##draft"));
+        assert_eq!(target_path(Path::new("a/b##host.x,tag.y")), Path::new("a/b"));
+        assert_eq!(target_path(Path::new("a##b/c")), Path::new("a##b/c"));
+
+        let conditions = parse_conditions("os.linux, tag.work,default").unwrap();
+        let expected =
+            [Condition::Os("linux".into()), Condition::Tag("work".into()), Condition::Default];
+        assert_eq!(conditions, expected);
+        assert_eq!(parse_conditions("os.linux,color.red"), Err("color.red".to_string()));
+        assert!(parse_conditions("os.").is_err());
+    }
+
+    #[test]
+    fn the_most_specific_match_wins() {
+        let names = ["x##default", "x##os.linux", "x##host.laptop", "x##os.macos,host.laptop"];
+        let (variant, reason) = chosen(&names, &[]);
+        assert_eq!(variant.as_deref(), Some("x##host.laptop"));
+        assert_eq!(reason, "host is laptop");
+
+        let (variant, _) = chosen(&["x##os.linux,tag.work", "x##os.linux"], &["work"]);
+        assert_eq!(variant.as_deref(), Some("x##os.linux,tag.work"));
+        let (variant, _) = chosen(&["x##os.linux,tag.work", "x##os.linux"], &[]);
+        assert_eq!(variant.as_deref(), Some("x##os.linux"));
+
+        let (variant, reason) = chosen(&["x##default", "x##os.macos"], &[]);
+        assert_eq!(variant.as_deref(), Some("x##default"));
+        assert_eq!(reason, "no other variant matches");
+
+        let (variant, reason) = chosen(&["x##os.macos"], &[]);
+        assert_eq!(variant, None);
+        assert_eq!(reason, "no variant matches");
+    }
+
+    #[test]
+    fn tags_do_not_add_up_to_a_more_specific_condition() {
+        let tags = ["a", "b", "c", "d"];
+        let (variant, _) = chosen(&["x##arch.x86_64", "x##tag.a,tag.b,tag.c"], &tags);
+        assert_eq!(variant.as_deref(), Some("x##arch.x86_64"));
+        let (variant, _) =
+            chosen(&["x##arch.x86_64,tag.a,tag.b,tag.c,tag.d", "x##os.linux"], &tags);
+        assert_eq!(variant.as_deref(), Some("x##os.linux"));
+
+        // Between the same kinds, more conditions are more specific
+        let (variant, _) = chosen(&["x##tag.a", "x##tag.a,tag.b"], &tags);
+        assert_eq!(variant.as_deref(), Some("x##tag.a,tag.b"));
+        let (variant, _) = chosen(&["x##os.linux", "x##os.linux,tag.a"], &tags);
+        assert_eq!(variant.as_deref(), Some("x##os.linux,tag.a"));
+    }
+}
@@ -1,52 +1,234 @@
-use std::env;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use dotao::{lock::RepoLock, util, DotaoError, DotaoResult, Engine, PartialConfig};
 
 use crate::{
     cli,
-    commands::{add::run_add_command, init::run_init_command, link::run_link_command},
-    util,
+    commands::{
+        add::run_add_command,
+        apply::run_apply_command,
+        export::run_export_command,
+        history::{run_history_command, run_undo_command},
+        import_chezmoi::run_import_chezmoi_command,
+        import_stow::run_import_stow_command,
+        init::{create_tree_file, run_init_command},
+        link::run_link_command,
+        list::{run_list_command, ListFilters},
+        merge::run_merge_driver_command,
+        migrate::run_migrate_command,
+        mv_group::run_mv_group_command,
+        reabsorb::run_reabsorb_command,
+        relink::run_relink_command,
+        status::run_status_command,
+        unlink::run_unlink_command,
+        watch::run_watch_command,
+        which::{run_edit_command, run_which_command},
+    },
 };
 
-fn run_status_command() {
-    let groups = util::load_groups_from_path("dotao.tsml");
-    crate::diff::StatusDiff::from_groups_map(&groups.map);
+fn run_remove_command() -> DotaoResult<()> {
+    Err(DotaoError::invalid_usage(
+        "`dotao remove` is not implemented yet",
+        "run `dotao unlink <group>`, then delete the group from the tree file by hand",
+    ))
+}
+
+// How the command was called, for the history
+fn command_line() -> String {
+    let args: Vec<String> = env::args().skip(1).collect();
+    format!("dotao {}", args.join(" "))
 }
 
-fn run_unlink_command() {
-    todo!()
+// Report a run that crashed while changing the repository
+fn warn_if_stale(lock: &RepoLock) {
+    if let Some(holder) = lock.stale() {
+        eprintln!(
+            "Warning: {} was interrupted while changing the repository, check it with `git \
+             status`.",
+            holder
+        );
+    }
 }
 
-fn run_remove_command() {
-    todo!()
+// For commands that change the repository, waits for the other ones to finish
+fn take_exclusive_lock(dotfiles_dir: &Path) -> DotaoResult<RepoLock> {
+    let lock = RepoLock::exclusive(dotfiles_dir, &command_line(), |holder| match holder {
+        Some(holder) => eprintln!("Waiting for {} to finish...", holder),
+        None => eprintln!("Waiting for another dotao command to finish..."),
+    })?;
+    warn_if_stale(&lock);
+    Ok(lock)
 }
 
-pub fn run_app() {
+// For commands that only read it, fails if it's being changed
+fn take_shared_lock(dotfiles_dir: &Path) -> DotaoResult<Option<RepoLock>> {
+    let lock = RepoLock::shared(dotfiles_dir)?;
+    lock.iter().for_each(warn_if_stale);
+    Ok(lock)
+}
+
+pub fn run_app() -> DotaoResult<()> {
     if env::args().len() == 1 {
-        run_status_command();
+        // Without clap, which reads `DOTAO_DIR` for the other commands
+        let explicit_dotfiles_dir = env::var("DOTAO_DIR").ok();
+        let dotfiles_dir = util::dotfiles_dir(explicit_dotfiles_dir.as_deref())?;
+        let _lock = take_shared_lock(&dotfiles_dir)?;
+        let engine = Engine::load(dotfiles_dir, PartialConfig::default())?;
+        return run_status_command(&engine, false);
     }
     let args = cli::parse_args();
 
+    // From `-C` or `DOTAO_DIR`, if any
+    let explicit_dotfiles_dir = args.value_of("dotfiles-dir");
+    // Lazily search for it, `init` and `merge-driver` don't need one
+    let dotfiles_dir = || util::dotfiles_dir(explicit_dotfiles_dir);
+    // Config files, env vars, and then CLI flags
+    let load_engine = || {
+        let engine = Engine::load(dotfiles_dir()?, PartialConfig::from_args(&args)?)?;
+        Ok(engine.with_command(command_line()))
+    };
+    // Held until the command returns
+    let lock_exclusive = || take_exclusive_lock(&dotfiles_dir()?);
+    let lock_shared = || take_shared_lock(&dotfiles_dir()?);
+
     match args.subcommand() {
-        ("status", Some(_)) => {
-            run_status_command();
+        ("status", Some(status_matches)) => {
+            let fail_untracked = status_matches.is_present("fail-untracked");
+            let _lock = lock_shared()?;
+            run_status_command(&load_engine()?, fail_untracked)
         },
         ("init", Some(init_matches)) => {
             // Flag
             let force = init_matches.is_present("force");
-            run_init_command(force);
+            let dir = match explicit_dotfiles_dir {
+                Some(dir) => PathBuf::from(dir),
+                None => util::current_dir()?,
+            };
+            run_init_command(&dir, force)
         },
         ("add", Some(add_matches)) => {
             let groups = add_matches.values_of("groups").unwrap(); // Safe
             let groups: Vec<&str> = groups.collect();
-            run_add_command(&groups);
+            let _lock = lock_exclusive()?;
+            run_add_command(&mut load_engine()?, &groups)
+        },
+        ("import-chezmoi", Some(import_matches)) => {
+            // Safe unwraps, all arguments are required
+            let source_dir = import_matches.value_of("source-dir").unwrap();
+            let group = import_matches.value_of("group").unwrap().trim_end_matches('/');
+            let _lock = lock_exclusive()?;
+            run_import_chezmoi_command(&mut load_engine()?, Path::new(source_dir), group)
+        },
+        ("import-stow", Some(import_matches)) => {
+            let stow_dir = import_matches.value_of("stow-dir").unwrap(); // Safe
+            let stow_dir = Path::new(stow_dir)
+                .canonicalize()
+                .map_err(|err| DotaoError::io("read", stow_dir, err))?;
+            // Only the tree file, the repository of the user is left as it is
+            let tree_file_path = stow_dir.join(util::TREE_FILE_NAME);
+            if !tree_file_path.is_file() {
+                create_tree_file(&tree_file_path)?;
+                println!("Created the tree file at '{}'.", util::to_utf(&tree_file_path));
+            }
+            let _lock = take_exclusive_lock(&stow_dir)?;
+            let engine = Engine::load(&stow_dir, PartialConfig::from_args(&args)?)?;
+            run_import_stow_command(&mut engine.with_command(command_line()))
         },
         ("link", Some(_)) => {
-            run_link_command();
+            let _lock = lock_exclusive()?;
+            run_link_command(&load_engine()?)
+        },
+        ("apply", Some(_)) => {
+            let _lock = lock_exclusive()?;
+            run_apply_command(&load_engine()?)
+        },
+        ("list", Some(list_matches)) => {
+            let filters = ListFilters {
+                tags: list_matches.values_of("tag").map(Iterator::collect).unwrap_or_default(),
+                // Safe unwrap, clap checks the possible values
+                states: list_matches
+                    .values_of("state")
+                    .map(|states| states.map(|state| state.parse().unwrap()).collect())
+                    .unwrap_or_default(),
+            };
+            let paths_only = list_matches.is_present("paths");
+            let _lock = lock_shared()?;
+            run_list_command(&load_engine()?, list_matches.value_of("group"), &filters, paths_only)
+        },
+        ("mv-group", Some(mv_matches)) => {
+            // Safe unwraps, all arguments are required
+            let old = mv_matches.value_of("old").unwrap();
+            let new = mv_matches.value_of("new").unwrap();
+            let _lock = lock_exclusive()?;
+            run_mv_group_command(&mut load_engine()?, old, new)
+        },
+        ("migrate", Some(_)) => {
+            let _lock = lock_exclusive()?;
+            run_migrate_command(&mut load_engine()?)
+        },
+        ("relink", Some(relink_matches)) => {
+            let previous: Vec<PathBuf> = relink_matches
+                .values_of("from")
+                .map(|dirs| dirs.map(PathBuf::from).collect())
+                .unwrap_or_default();
+            let yes = relink_matches.is_present("yes");
+            let _lock = lock_exclusive()?;
+            run_relink_command(&load_engine()?, &previous, yes)
+        },
+        ("reabsorb", Some(reabsorb_matches)) => {
+            let paths: Vec<PathBuf> = reabsorb_matches
+                .values_of("paths")
+                .map(|paths| paths.map(PathBuf::from).collect())
+                .unwrap_or_default();
+            let _lock = lock_exclusive()?;
+            run_reabsorb_command(&load_engine()?, &paths)
+        },
+        ("which", Some(which_matches)) => {
+            let path = which_matches.value_of("path").unwrap(); // Safe
+            let _lock = lock_shared()?;
+            run_which_command(&load_engine()?, Path::new(path))
+        },
+        ("edit", Some(edit_matches)) => {
+            let path = edit_matches.value_of("path").unwrap(); // Safe
+            let _lock = lock_exclusive()?;
+            run_edit_command(&load_engine()?, Path::new(path))
+        },
+        ("watch", Some(_)) => run_watch_command(load_engine, lock_exclusive),
+        ("export", Some(export_matches)) => {
+            // Safe unwraps, required, and has a default value checked by clap
+            let output = export_matches.value_of("output").unwrap();
+            let format = export_matches.value_of("format").unwrap().parse().unwrap();
+            let _lock = lock_shared()?;
+            run_export_command(&load_engine()?, format, Path::new(output))
+        },
+        ("history", Some(_)) => {
+            let _lock = lock_shared()?;
+            run_history_command(&dotfiles_dir()?)
+        },
+        ("undo", Some(undo_matches)) => {
+            // Safe unwrap, has a default value and a validator
+            let amount = undo_matches.value_of("amount").unwrap().parse().unwrap();
+            let _lock = lock_exclusive()?;
+            run_undo_command(&dotfiles_dir()?, amount, &command_line())
         },
-        ("unlink", Some(_)) => {
-            run_unlink_command();
+        ("unlink", Some(unlink_matches)) => {
+            let groups: Vec<&str> = unlink_matches
+                .values_of("groups")
+                .map(|groups| groups.map(|group| group.trim_end_matches('/')).collect())
+                .unwrap_or_default();
+            let _lock = lock_exclusive()?;
+            run_unlink_command(&load_engine()?, &groups)
         },
-        ("rm", Some(_)) => {
-            run_remove_command();
+        ("remove", Some(_)) => run_remove_command(),
+        ("merge-driver", Some(merge_matches)) => {
+            // Safe unwraps, all arguments are required
+            let base = merge_matches.value_of("base").unwrap();
+            let ours = merge_matches.value_of("ours").unwrap();
+            let theirs = merge_matches.value_of("theirs").unwrap();
+            run_merge_driver_command(base, ours, theirs)
         },
         _ => unreachable!(),
     }
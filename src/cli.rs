@@ -6,6 +6,48 @@ pub fn parse_args() -> clap::ArgMatches<'static> {
         .version(crate_version!())
         .help_message("Display help information.")
         .version_message("Display version information.")
+        .arg(
+            Arg::with_name("dotfiles-dir")
+                .short("C")
+                .long("dotfiles-dir")
+                .value_name("DIR")
+                .env("DOTAO_DIR")
+                .global(true)
+                .help(
+                    "Dotfiles folder to use, by default, searches for 'dotao.tsml' in the \
+                     current directory and its parents.",
+                ),
+        )
+        .arg(
+            Arg::with_name("target-root")
+                .long("target-root")
+                .value_name("DIR")
+                .global(true)
+                .help("Where links are created, overrides the config files."),
+        )
+        .arg(
+            Arg::with_name("link-style")
+                .long("link-style")
+                .value_name("STYLE")
+                .possible_values(&["relative", "absolute"])
+                .global(true)
+                .help("Write link targets as relative or absolute paths."),
+        )
+        .arg(
+            Arg::with_name("tags")
+                .long("tags")
+                .value_name("TAGS")
+                .global(true)
+                .help("Comma separated active tags, entries with other tags are skipped."),
+        )
+        .arg(
+            Arg::with_name("conflict")
+                .long("conflict")
+                .value_name("STRATEGY")
+                .possible_values(&["abort", "skip", "overwrite"])
+                .global(true)
+                .help("What to do when something is in the place of a link."),
+        )
         .subcommand(
             SubCommand::with_name("add")
                 .settings(&[AppSettings::ColoredHelp])
@@ -30,17 +72,156 @@ pub fn parse_args() -> clap::ArgMatches<'static> {
                 .aliases(&["r", "rm", "delete"])
                 .about("Remove groups from the tree file."),
         )
+        .subcommand(
+            SubCommand::with_name("import-chezmoi")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("source-dir")
+                        .required(true)
+                        .help("chezmoi source directory, see `chezmoi source-path`."),
+                )
+                .arg(Arg::with_name("group").required(true).help("New group to import into."))
+                .about("Copy a chezmoi source directory into a new group, with the real names."),
+        )
+        .subcommand(
+            SubCommand::with_name("import-stow")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("stow-dir")
+                        .required(true)
+                        .help("Stow directory, it becomes the dotfiles folder."),
+                )
+                .about("Add each package of a Stow directory as a group, keeping Stow's links."),
+        )
         .subcommand(
             SubCommand::with_name("link")
                 .settings(&[AppSettings::ColoredHelp])
                 .alias("l")
                 .about("Link groups in the tree file."),
         )
+        .subcommand(SubCommand::with_name("apply").settings(&[AppSettings::ColoredHelp]).about(
+            "Link everything, solving conflicts with the configured strategy, and run hooks, \
+             does nothing if already up to date.",
+        ))
+        .subcommand(
+            SubCommand::with_name("mv-group")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(Arg::with_name("old").required(true).help("Current group name."))
+                .arg(Arg::with_name("new").required(true).help("New group name."))
+                .about("Rename a group and update its links."),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .settings(&[AppSettings::ColoredHelp])
+                .about("Rewrite the tree file to the current syntax version, keeping comments."),
+        )
+        .subcommand(
+            SubCommand::with_name("relink")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("DIR")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Previous location of the dotfiles folder, can be repeated."),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Don't ask for confirmation after the preview"),
+                )
+                .about(
+                    "Repair links that point to a previous location of the dotfiles folder, or \
+                     that don't follow the link style.",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("reabsorb")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("paths")
+                        .multiple(true)
+                        .help("Only these replaced links, by target path, all of them if none."),
+                )
+                .about(
+                    "Move the content of files that replaced links back into their groups, and \
+                     restore the links.",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("which")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(Arg::with_name("path").required(true).help("Target or source path."))
+                .about("Show the group, source and tree file line that provide a path."),
+        )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(Arg::with_name("path").required(true).help("Target or source path."))
+                .alias("e")
+                .about("Open the source of a path in $EDITOR, and link it if needed."),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .settings(&[AppSettings::ColoredHelp])
+                .alias("w")
+                .about("Update the tree file and link new files as group folders change."),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Archive (or directory) to write to."),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(&["tar", "dir"])
+                        .default_value("tar")
+                        .help("Write a tar archive, or into a staging directory."),
+                )
+                .about(
+                    "Write the files of the active tags as they'd appear under the target root, \
+                     without links.",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .settings(&[AppSettings::ColoredHelp])
+                .about("Show the operations done by previous commands."),
+        )
+        .subcommand(
+            SubCommand::with_name("undo")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("amount")
+                        .default_value("1")
+                        .validator(|amount| match amount.parse::<usize>() {
+                            Ok(amount) if amount > 0 => Ok(()),
+                            _ => Err("expected a positive number".to_string()),
+                        })
+                        .help("How many operations to undo."),
+                )
+                .about("Reverse the last operations, if nothing changed since they ran."),
+        )
         .subcommand(
             SubCommand::with_name("unlink")
                 .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("groups")
+                        .multiple(true)
+                        .help("Groups to unlink, all of them if none is given."),
+                )
                 .alias("u")
-                .about("Unlink groups in the tree file."),
+                .about("Remove the links of groups in the tree file, aliases included."),
         )
         .subcommand(
             SubCommand::with_name("init")
@@ -57,7 +238,52 @@ pub fn parse_args() -> clap::ArgMatches<'static> {
         .subcommand(
             SubCommand::with_name("status")
                 .settings(&[AppSettings::ColoredHelp])
+                .arg(
+                    Arg::with_name("fail-untracked")
+                        .long("fail-untracked")
+                        .help("Fail if group folders have files missing from the tree file"),
+                )
                 .about("Show status of the dotfiles."),
         )
+        .subcommand(
+            SubCommand::with_name("list")
+                .settings(&[AppSettings::ColoredHelp])
+                .arg(Arg::with_name("group").help("Only list this group."))
+                .arg(
+                    Arg::with_name("tag")
+                        .long("tag")
+                        .short("t")
+                        .value_name("TAG")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only list entries with this tag, can be repeated."),
+                )
+                .arg(
+                    Arg::with_name("state")
+                        .long("state")
+                        .short("s")
+                        .value_name("STATE")
+                        .possible_values(&["linked", "pending", "conflict", "missing", "inactive"])
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only list entries in this state, can be repeated."),
+                )
+                .arg(
+                    Arg::with_name("paths")
+                        .long("paths")
+                        .short("p")
+                        .help("Print one target path per line, for piping."),
+                )
+                .alias("ls")
+                .about("List groups and the state of their entries."),
+        )
+        .subcommand(
+            SubCommand::with_name("merge-driver")
+                .settings(&[AppSettings::ColoredHelp, AppSettings::Hidden])
+                .arg(Arg::with_name("base").required(true).help("Common ancestor version (%O)."))
+                .arg(Arg::with_name("ours").required(true).help("Current version (%A)."))
+                .arg(Arg::with_name("theirs").required(true).help("Other branch version (%B)."))
+                .about("Git merge driver for the tree file, set up by `dotao init`."),
+        )
         .get_matches()
 }
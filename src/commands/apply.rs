@@ -0,0 +1,31 @@
+use dotao::{error::DotaoResult, Engine};
+
+use super::link::link;
+
+// `link`, with the facts of the machine, the active tags and a summary, a no-op if the machine is
+// up to date
+pub fn run_apply_command(engine: &Engine) -> DotaoResult<()> {
+    let config = engine.config();
+    let facts = engine.facts();
+    let host = if facts.host.is_empty() { "unknown" } else { &facts.host };
+    println!("Running on {} {}, host {}.", facts.os, facts.arch, host);
+    if config.default_tags.is_empty() {
+        println!("No tags active.");
+    } else {
+        println!("Active tags: {}.", config.default_tags.join(", "));
+    }
+
+    let (plan, report) = link(engine)?;
+
+    println!("Summary:");
+    println!("    {} already linked", plan.already_linked.len());
+    println!("    {} linked", report.linked.len());
+    println!("    {} replaced", report.replaced.len());
+    println!("    {} mode(s) set", report.modes_set.len());
+    println!("    {} conflict(s) skipped", report.skipped.len());
+    println!("    {} hook(s) run", report.hooks_run.len());
+    for command in report.hooks_run.iter() {
+        println!("        {}", command);
+    }
+    Ok(())
+}
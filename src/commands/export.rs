@@ -0,0 +1,18 @@
+use std::path::Path;
+
+use dotao::{
+    error::DotaoResult,
+    export::{self, ExportFormat},
+    util::to_utf,
+    Engine,
+};
+
+// Write the resolved files of the active tags, for machines without dotao
+pub fn run_export_command(engine: &Engine, format: ExportFormat, output: &Path) -> DotaoResult<()> {
+    let report = export::export(engine, format, output)?;
+    for group in report.skipped_groups.iter() {
+        eprintln!("Skipped system group '{}', its files aren't under the target root.", group);
+    }
+    println!("Exported {} file(s) to '{}'.", report.files.len(), to_utf(output));
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use dotao::{
+    error::DotaoResult,
+    history::{self, Action, Operation},
+    util::to_utf,
+};
+
+fn describe_action(action: &Action) -> String {
+    match action {
+        Action::CreateLink { location, target } => {
+            format!("created link '{}' -> '{}'", to_utf(location), to_utf(target))
+        },
+        Action::RemoveLink { location, target } => {
+            format!("removed link '{}' -> '{}'", to_utf(location), to_utf(target))
+        },
+        Action::RemoveFile { location, backup } => {
+            format!("removed '{}' (backup at '{}')", to_utf(location), to_utf(backup))
+        },
+        Action::RenameDir { from, to } => {
+            format!("renamed '{}' to '{}'", to_utf(from), to_utf(to))
+        },
+        Action::WriteFile { path, .. } => format!("wrote '{}'", to_utf(path)),
+        Action::SetMode { path, before, after } => {
+            format!("changed mode of '{}' from {:04o} to {:04o}", to_utf(path), before, after)
+        },
+    }
+}
+
+// Every operation in the log, oldest first, with its actions
+pub fn run_history_command(dotfiles_dir: &Path) -> DotaoResult<()> {
+    let history = history::load_history(dotfiles_dir)?;
+    if history.is_empty() {
+        println!("No operations recorded yet.");
+        return Ok(());
+    }
+
+    let undoable: Vec<u64> =
+        history::undoable_operations(&history).iter().map(|operation| operation.id).collect();
+    for operation in history.iter() {
+        let Operation { id, command, timestamp, actions, undoes, partially_undoes } = operation;
+        let mut line = format!("#{}  {}  {}", id, history::format_timestamp(*timestamp), command);
+        if operation.is_undo() {
+            let mut ids: Vec<String> = undoes.iter().map(|id| format!("#{}", id)).collect();
+            if let Some((id, reversed)) = partially_undoes {
+                ids.push(format!("{} action(s) of #{}", reversed, id));
+            }
+            line.push_str(&format!("  (undid {})", ids.join(", ")));
+        } else if !undoable.contains(id) {
+            line.push_str("  (undone)");
+        }
+        println!("{}", line);
+        for action in actions.iter() {
+            println!("    {}", describe_action(action));
+        }
+    }
+    Ok(())
+}
+
+pub fn run_undo_command(dotfiles_dir: &Path, amount: usize, command: &str) -> DotaoResult<()> {
+    for id in history::undo(dotfiles_dir, amount, command)? {
+        println!("Undid operation #{}.", id);
+    }
+    Ok(())
+}
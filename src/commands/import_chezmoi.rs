@@ -0,0 +1,23 @@
+use std::path::Path;
+
+use dotao::{error::DotaoResult, import, util::to_utf, Engine};
+
+// Copy a chezmoi source directory into a new group, reporting what couldn't be translated
+pub fn run_import_chezmoi_command(
+    engine: &mut Engine,
+    source_dir: &Path,
+    group: &str,
+) -> DotaoResult<()> {
+    let report = import::import_chezmoi(engine, source_dir, group)?;
+    println!("Imported {} file(s) from chezmoi into group '{}'.", report.files, group);
+
+    if !report.untranslated.is_empty() {
+        eprintln!("Not translated:");
+    }
+    for untranslated in report.untranslated.iter() {
+        let result = if untranslated.imported { "imported without it" } else { "skipped" };
+        eprintln!("    '{}': {}, {}.", to_utf(&untranslated.path), untranslated.reason, result);
+    }
+    println!("Run `dotao status` to see what's left to link.");
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use dotao::{error::DotaoResult, import, util::to_utf, Engine};
+
+// Add the packages of the Stow directory (the dotfiles folder) as groups, keeping Stow's links
+pub fn run_import_stow_command(engine: &mut Engine) -> DotaoResult<()> {
+    let report = import::import_stow(engine)?;
+    for package in report.local_ignores.iter() {
+        eprintln!(
+            "Package '{}' has its own '.stow-local-ignore', which isn't supported, Stow's default \
+             ignore list was used instead.",
+            package
+        );
+    }
+    println!(
+        "Imported {} Stow package(s) as groups: {}.",
+        report.packages.len(),
+        report.packages.join(", ")
+    );
+
+    // Stow links into the parent of its directory by default
+    let stow_target = engine.dotfiles_dir().parent().unwrap_or(engine.dotfiles_dir());
+    let target_root = &engine.config().target_root;
+    if stow_target != target_root {
+        eprintln!(
+            "Stow links into '{}' by default, but the target root is '{}', set `target_root` in \
+             the config if that's where your packages were stowed.",
+            to_utf(stow_target),
+            to_utf(target_root)
+        );
+    }
+
+    let diff = engine.status()?;
+    println!("{} file(s) already linked by Stow.", diff.linked_correctly.len());
+    if !diff.ready_to_link.is_empty() || !diff.is_clear() {
+        println!("Run `dotao status` to see what's left to link.");
+    }
+    Ok(())
+}
@@ -1,84 +1,196 @@
-use std::{fs, io::Write, path::Path};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    process::Command,
+};
 
+use dotao::{
+    error::{DotaoError, DotaoResult},
+    util::{
+        ask_for_confirmation, home_dir, is_dotfiles_folder, is_in_git_repository, to_utf,
+        LOCAL_STATE_DIR, TREE_FILE_NAME,
+    },
+};
 use indoc::indoc;
 
-use crate::{
-    error,
-    util::{current_dir, is_in_dotfiles_folder, to_utf},
-};
+// Run git with `args` inside of `dir`
+fn run_git(dir: &Path, args: &[&str]) -> DotaoResult<()> {
+    let command_error =
+        |reason: String| DotaoError::Command { command: format!("git {}", args.join(" ")), reason };
+
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .map_err(|err| command_error(err.to_string()))?;
+
+    if !status.success() {
+        return Err(command_error(status.to_string()));
+    }
+    Ok(())
+}
+
+// Append `line` to the file at `path` if it isn't there yet, creating the file if needed
+fn append_line_if_missing(path: impl AsRef<Path>, line: &str) -> DotaoResult<()> {
+    let path = path.as_ref();
+    let content = if path.exists() {
+        fs::read_to_string(path).map_err(|err| DotaoError::io("read", path, err))?
+    } else {
+        String::new()
+    };
+
+    if content.lines().any(|existing| existing.trim() == line) {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| DotaoError::io("open for writing", path, err))?;
+
+    // Don't glue our line to the last one if the file has no trailing newline
+    let separator = if content.is_empty() || content.ends_with('\n') { "" } else { "\n" };
+    writeln!(file, "{}{}", separator, line).map_err(|err| DotaoError::io("write to", path, err))
+}
+
+// Ignore local state, and let `dotao merge-driver` handle merges of the tree file
+fn configure_git_repository(dir: &Path) -> DotaoResult<()> {
+    append_line_if_missing(dir.join(".gitignore"), &format!("/{}/", LOCAL_STATE_DIR))?;
+    append_line_if_missing(dir.join(".gitattributes"), &format!("{} merge=dotao", TREE_FILE_NAME))?;
+
+    run_git(dir, &["config", "merge.dotao.name", "dotao tree file merge driver"])?;
+    run_git(dir, &["config", "merge.dotao.driver", "dotao merge-driver %O %A %B"])
+}
+
+// Create the tree file inside of `dir`
+pub fn run_init_command(dir: &Path, force_flag: bool) -> DotaoResult<()> {
+    let dir = dir.canonicalize().map_err(|err| DotaoError::io("initialize at", dir, err))?;
+    let tree_file_path = dir.join(TREE_FILE_NAME);
 
-pub fn run_init_command(force_flag: bool) {
     // Checks
-    if !is_in_dotfiles_folder() && !force_flag {
-        error!(
-            "You are not inside the '~/dotfiles' folder, we recomend creating it and running `dotao` in it.\n\
-             To ignore this recommendation, run `dotao init --force` instead."
-        );
-    } else if Path::new("dotao.tsml").exists() {
-        error!(
-            "You ran `dotao init`, but the 'dotao.tsml' file already exists!\n\
-             Delete the file manually if you with to restart the tree configuration.\n\
-             \n\
-             This action might be irreversible."
-        );
+    if dir == home_dir()?
+        && !ask_for_confirmation(
+            "You are about to turn your HOME directory into the dotfiles folder, are you sure?",
+        )?
+    {
+        return Err(DotaoError::Aborted("refusing to initialize inside of HOME".to_string()));
+    } else if !is_dotfiles_folder(&dir) && !force_flag {
+        return Err(DotaoError::invalid_usage(
+            "you are not inside the '~/dotfiles' folder, we recomend creating it and running \
+             `dotao` in it",
+            "to ignore this recommendation, run `dotao init --force` instead",
+        ));
+    } else if tree_file_path.exists() {
+        return Err(DotaoError::invalid_usage(
+            "you ran `dotao init`, but the 'dotao.tsml' file already exists",
+            "delete the file manually if you wish to restart the tree configuration, this \
+             action might be irreversible",
+        ));
     }
 
-    let mut new_dotao_tsml = fs::File::create("dotao.tsml")
-        .unwrap_or_else(|err| error!("Error while trying to create file 'dotao.tsml': {}.", err));
+    let mut in_git_repository = is_in_git_repository(&dir);
+    if !in_git_repository && !force_flag {
+        if ask_for_confirmation("There's no git repository here, do you want to run `git init`?")? {
+            run_git(&dir, &["init"])?;
+            in_git_repository = true;
+        } else {
+            return Err(DotaoError::invalid_usage(
+                "dotao is designed to be used inside of a git repository",
+                "to create the tree file anyway, run `dotao init --force` instead",
+            ));
+        }
+    }
 
-    write!(
-        new_dotao_tsml,
-        indoc!(
-            "//       __     __              __
-             //   ___/ /__  / /____ ____    / /________ ___
-             //  / _  / _ \\/ __/ _ `/ _ \\  / __/ __/ -_) -_)
-             //  \\___/\\___/\\__/\\___/\\___/  \\__/_/  \\__/\\__/
-             //
-             // Welcome to the dotao tree!
-             //
-             // THis file is used by dotao to keep track of dotfiles, when you run `dotao add` or `dotao remove`, files
-             // are added and removed from here. If you're using git, add and push this too.
-             //
-             // If you're lost, check the quickstart guide (TODO) at https://github.com/marcospb19/dotao
-             //
-             // You can edit this file manually too, but this is not strictly necessary, as `dotao` might have all the commands needed.
-             //
-             // ---
-             // # Syntax
-             // Group syntax:
-             // - [group_name]
-             //
-             // File syntax:
-             // \"file_name\"
-             //
-             // Directory syntax:
-             // \"directory_name\": [
-             //     \"file_a\"
-             //     \"nested_directory\": [
-             //         \"file_1\"
-             //         \"file_2\"
-             //         \"file_3\"
-             //         \"file_4\"
-             //     ]
-             //     \"file_b\"
-             //     \"empty_directory\": []
-             // ]
-             //
-             // Tag syntax:
-             // (tag_name) [Token] // Where token is a file or directory
-             // or
-             // (tag_name)
-             // - [group_name] // When applying to a group
-             //
-            "
-        )
-    )
-    .unwrap_or_else(|err| error!("Error while trying to write text to 'dotao.tsml': {}.", err));
+    create_tree_file(&tree_file_path)?;
+
+    if in_git_repository {
+        configure_git_repository(&dir)?;
+    }
 
     // Success!
-    println!("Tree file successfully created at '{}'.", to_utf(current_dir().join("dotao.tsml")));
+    println!("Tree file successfully created at '{}'.", to_utf(&tree_file_path));
     println!(
         "For help, type `dotao --help`.\n\
          See also the (TODO) full tutorial at https://github.com/marcospb19/dotao ."
     );
+    Ok(())
+}
+
+// Write a new tree file at `tree_file_path`, with the syntax explained in its comments
+pub fn create_tree_file(tree_file_path: &Path) -> DotaoResult<()> {
+    let mut new_dotao_tsml = fs::File::create(tree_file_path)
+        .map_err(|err| DotaoError::io("create", tree_file_path, err))?;
+
+    write!(
+        new_dotao_tsml,
+        indoc!(
+            "# tsml-version: 2
+             #       __     __              __
+             #   ___/ /__  / /____ ____    / /________ ___
+             #  / _  / _ \\/ __/ _ `/ _ \\  / __/ __/ -_) -_)
+             #  \\___/\\___/\\__/\\___/\\___/  \\__/_/  \\__/\\__/
+             #
+             # Welcome to the dotao tree!
+             #
+             # THis file is used by dotao to keep track of dotfiles, when you run `dotao add` or `dotao remove`, files
+             # are added and removed from here. If you're using git, add and push this too.
+             #
+             # If you're lost, check the quickstart guide (TODO) at https://github.com/marcospb19/dotao
+             #
+             # You can edit this file manually too, but this is not strictly necessary, as `dotao` might have all the commands needed.
+             #
+             # ---
+             # Syntax
+             # Group syntax:
+             # - [group_name]
+             #
+             # File syntax:
+             # \"file_name\"
+             #
+             # Directory syntax:
+             # \"directory_name\": [
+             #     \"file_a\"
+             #     \"nested_directory\": [
+             #         \"file_1\"
+             #         \"file_2\"
+             #         \"file_3\"
+             #         \"file_4\"
+             #     ]
+             #     \"file_b\"
+             #     \"empty_directory\": []
+             # ]
+             #
+             # Tag syntax:
+             # (tag_name) [Token] # Where token is a file or directory
+             # or
+             # (tag_name)
+             # - [group_name] # When applying to a group
+             #
+             # Mode syntax (for files, inside of the tag list):
+             # (mode=0600) \"file_name\"
+             # (tag_name, mode=0600) \"file_name\"
+             #
+             # Alias syntax (more paths to link the file at, inside of the tag list):
+             # (alias=.config/app/file_name) \"file_name\"
+             #
+             # Alternates (the best match is linked at \"file_name\"):
+             # \"file_name##os.linux\"
+             # \"file_name##host.laptop,tag.work\"
+             # \"file_name##default\"
+             #
+             # Hidden prefix syntax (\"dot_file_name\" is linked at \".file_name\"):
+             # (hidden=dot_)
+             # - [group_name]
+             #
+             # Comments start with '#', and go until the end of the line.
+             #
+             # Symlink syntax (the link in your system points to the target directly):
+             # \"link_name\" > \"target\"
+             #
+            "
+        )
+    )
+    .map_err(|err| DotaoError::io("write to", tree_file_path, err))
 }
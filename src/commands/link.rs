@@ -1,90 +1,78 @@
-use std::{
-    iter,
-    os::unix::fs::symlink,
-    path::{Path, PathBuf},
+use dotao::{
+    config::ConflictStrategy,
+    engine::{ApplyReport, Engine, Plan},
+    error::{DotaoError, DotaoResult},
+    util::{ask_for_confirmation, to_utf},
 };
 
-use crate::{
-    diff::StatusDiff,
-    error,
-    util::{self, to_utf},
-};
+// Show the commands that would run through the escalation command, and ask before running them
+pub fn confirm_privileged_commands(engine: &Engine, plan: &Plan) -> DotaoResult<()> {
+    let commands = engine.privileged_commands(plan);
+    if commands.is_empty() {
+        return Ok(());
+    }
 
-pub fn construct_link_target(file_path: impl AsRef<Path>, group_path: impl AsRef<Path>) -> PathBuf {
-    let directory_level = file_path.as_ref().components().count();
-    let mut path = iter::repeat("..").take(directory_level - 1).collect::<PathBuf>(); // Subtraction could fail
-    path.push("dotfiles");
-    path.push(group_path);
-    path.push(file_path);
-    path
+    println!("Linking system groups needs privileges, these commands will be run:");
+    for command in commands.iter() {
+        let command: Vec<String> = command.iter().map(to_utf).collect();
+        println!("    {}", command.join(" "));
+    }
+    if !ask_for_confirmation("Run them?")? {
+        return Err(DotaoError::Aborted("refusing to run privileged commands".to_string()));
+    }
+    Ok(())
 }
 
-// Link everything to the backup_dir
-pub fn run_link_command() {
-    let groups = tsml::Groups::from_path("dotao.tsml").unwrap();
-
-    let diff = StatusDiff::from_groups_map(&groups.map);
-    let can_link = diff.is_clear();
-
-    for _ in diff.linked_incorrectly.iter() {
-        eprintln!("Err: There is already a link at 'lilili', but it links to another file:");
-        eprintln!("    found 'lalal', instead of: 'lele'.");
-        // eprintln!("    found '{}', instead of: '{}'.", );
+// Link everything to the target root with the configured strategy, printing conflicts and
+// blockers, shared by `link` and `apply`
+pub fn link(engine: &Engine) -> DotaoResult<(Plan, ApplyReport)> {
+    let plan = engine.plan(engine.config().conflict_strategy)?;
+    for conflict in plan.conflicts.iter() {
+        eprintln!("{}", conflict);
     }
 
-    for x in diff.no_permission_to_link_to_target.iter() {
-        eprintln!("Permission denied to apply links to '{}'.", to_utf(x.path()));
+    if plan.is_empty() && !plan.is_blocked() {
+        println!("nothing to link, ok!");
+        let report = ApplyReport { skipped: plan.conflicts.clone(), ..Default::default() };
+        return Ok((plan, report));
     }
-
-    for _ in diff.not_a_symlink.iter() {
-        // Another file, what file?
+    if plan.is_blocked() {
+        if plan.strategy == ConflictStrategy::Overwrite {
+            eprintln!("Some conflicts can't be solved by overwriting.");
+        }
+        return Err(DotaoError::Conflicts { amount: plan.conflicts.len() });
     }
 
-    if !can_link {
-        error!("Aborting.");
+    // Every blocker at once, before touching anything
+    let blockers = engine.preflight(&plan);
+    for (dir, blockers) in blockers.iter() {
+        eprintln!("Blocked at '{}':", to_utf(dir));
+        for blocker in blockers {
+            eprintln!("    {}", blocker);
+        }
     }
-
-    if diff.ready_to_link.is_empty() {
-        println!("nothing to link, ok!");
-        return;
+    if !blockers.is_empty() {
+        return Err(DotaoError::Blocked { amount: blockers.values().map(Vec::len).sum() });
     }
 
-    for (file, group_name) in diff.ready_to_link.iter() {
-        let backup_dir = util::backup_dir();
-        let destination_location = backup_dir.join(file.path());
+    confirm_privileged_commands(engine, &plan)?;
+    let report = engine.apply(&plan)?;
 
-        let link_relative_target_path = construct_link_target(file.path(), group_name);
-        symlink(link_relative_target_path, destination_location).unwrap();
+    for path in report.replaced.iter() {
+        println!("Replaced '{}'.", to_utf(path));
+    }
+    for mode in plan.modes.iter() {
+        println!("Set mode {:04o} of '{}'.", mode.mode, to_utf(&mode.source));
+    }
+    if !report.skipped.is_empty() {
+        eprintln!("Skipped {} conflicting file(s).", report.skipped.len());
     }
-    //     match file {
-    //         tsml::FileTree::Regular { .. } => {
-    //             symlink(&dotfiles_path, &backup_dir_file_path).unwrap_or_else(|err| {
-    //                 error!(
-    //                     "Error while trying to make link for regular file '{}' -> '{}': {}",
-    //                     dotfiles_path.display(),
-    //                     home_path.display(),
-    //                     err
-    //                 )
-    //             });
-    //         },
-    //         tsml::FileTree::Directory { children, .. } => {
-    //             // Verify if should link or create directory
-    //             // If there's no children, link, else, create directory
-    //             if children.is_empty() {
-    //                 symlink(&dotfiles_path, &home_path).unwrap_or_else(|err| {
-    //                     error!(
-    //                         "Error while trying to make link for directory '{}' -> '{}': {}",
-    //                         dotfiles_path.display(),
-    //                         home_path.display(),
-    //                         err
-    //                     );
-    //                 });
-    //             } else {
-    //                 fs::create_dir_all(&home_path)
-    //                     .expect("Error while trying to create directory.");
-    //             }
-    //         },
-    //         tsml::FileTree::Symlink { .. } => todo!(),
-    //     }
-    // }
+    if !report.linked.is_empty() || report.modes_set.is_empty() {
+        println!("Linked {} file(s).", report.linked.len());
+    }
+    Ok((plan, report))
+}
+
+pub fn run_link_command(engine: &Engine) -> DotaoResult<()> {
+    link(engine).map(drop)
 }
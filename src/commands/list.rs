@@ -0,0 +1,188 @@
+use dotao::{
+    diff::{EntryState, StatusDiff},
+    engine::link_locations,
+    error::{DotaoError, DotaoResult},
+    util::to_utf,
+    Engine,
+};
+use tsml::{FileTree, GroupsMap};
+
+// Entries are kept if they have all of `tags`, and one of `states` (if any)
+pub struct ListFilters<'a> {
+    pub tags: Vec<&'a str>,
+    pub states: Vec<EntryState>,
+}
+
+impl ListFilters<'_> {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.states.is_empty()
+    }
+
+    // `file` must be an entry of the map `diff` was created from
+    fn matches(&self, file: &FileTree, diff: &StatusDiff) -> bool {
+        let tags = effective_tags(file);
+        self.tags.iter().all(|tag| tags.iter().any(|other| other == tag))
+            && (self.states.is_empty() || self.states.contains(&diff.state_of(file)))
+    }
+}
+
+// Every group of `map`, or only `group`, if given
+fn selected_groups<'a>(
+    map: &'a GroupsMap,
+    group: Option<&str>,
+) -> DotaoResult<Vec<(&'a String, &'a Vec<FileTree>)>> {
+    let name = match group {
+        Some(name) => name.trim_end_matches('/'),
+        None => return Ok(map.iter().collect()),
+    };
+    let group = map.get_key_value(name).ok_or_else(|| {
+        DotaoError::invalid_usage(
+            format!("there's no group '{}' in the tree file", name),
+            "run `dotao list` to see every group",
+        )
+    })?;
+    Ok(vec![group])
+}
+
+fn effective_tags(file: &FileTree) -> Vec<&String> {
+    file.extra()
+        .as_ref()
+        .map(|tags| tags.effective_tags().into_iter().collect())
+        .unwrap_or_default()
+}
+
+fn file_name(file: &FileTree) -> String {
+    file.path().file_name().map(to_utf).unwrap_or_else(|| to_utf(file.path()))
+}
+
+// Print groups as indented trees, or just the target paths if `paths_only`
+pub fn run_list_command(
+    engine: &Engine,
+    group: Option<&str>,
+    filters: &ListFilters,
+    paths_only: bool,
+) -> DotaoResult<()> {
+    let diff = engine.status()?;
+
+    let groups = selected_groups(&engine.groups().map, group)?;
+
+    let is_match = |file: &FileTree| filters.matches(file, &diff);
+    // Directories are shown if anything inside of them is
+    let has_match = |file: &FileTree| file.files().skip_dirs(true).any(is_match);
+
+    for (name, trees) in groups {
+        // Aliases included
+        let locations = |file: &FileTree| {
+            let locations = link_locations(file, name, engine.config());
+            locations.iter().map(to_utf).collect::<Vec<_>>().join(", ")
+        };
+        if paths_only {
+            for file in trees.iter().flat_map(|tree| tree.files().skip_dirs(true)) {
+                if is_match(file) {
+                    for location in link_locations(file, name, engine.config()) {
+                        println!("{}", to_utf(location));
+                    }
+                }
+            }
+            continue;
+        }
+
+        if !filters.is_empty() && !trees.iter().any(has_match) {
+            continue;
+        }
+        println!("[{}]", name);
+
+        for tree in trees {
+            let mut files = tree.files();
+            while let Some(file) = files.next() {
+                // Nested one level below the group
+                let indentation = "    ".repeat(files.depth() + 1);
+
+                if file.is_dir() {
+                    if filters.is_empty() || has_match(file) {
+                        println!("{}{}/", indentation, file_name(file));
+                    }
+                    continue;
+                }
+                if !is_match(file) {
+                    continue;
+                }
+
+                let tags = effective_tags(file);
+                let tags = if tags.is_empty() {
+                    String::new()
+                } else {
+                    let tags: Vec<&str> = tags.iter().map(|tag| tag.as_str()).collect();
+                    format!(" ({})", tags.join(", "))
+                };
+                println!(
+                    "{}{} -> {} [{}]{}",
+                    indentation,
+                    file_name(file),
+                    locations(file),
+                    diff.state_of(file),
+                    tags
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use dotao::diff::EntryLink;
+
+    use super::*;
+
+    fn groups() -> tsml::Groups {
+        let text = "- [zsh]\n(work) \".zshrc\"\n\".zshenv\"\n- [git]\n(work) \".gitconfig\"\n";
+        let mut groups = tsml::Groups::from_text(text).unwrap();
+        // Empty, for the entries before the first group
+        groups.map.remove("main");
+        groups
+    }
+
+    fn entry<'a>(groups: &'a tsml::Groups, group: &str, path: &str) -> &'a FileTree {
+        let files = groups.map[group].iter().flat_map(|tree| tree.files().skip_dirs(true));
+        files.into_iter().find(|file| file.path() == Path::new(path)).unwrap()
+    }
+
+    // Paths of the entries kept by `filters`
+    fn matching(groups: &tsml::Groups, diff: &StatusDiff, filters: &ListFilters) -> Vec<String> {
+        let files = groups.map.values().flatten().flat_map(|tree| tree.files().skip_dirs(true));
+        files.filter(|file| filters.matches(file, diff)).map(|file| to_utf(file.path())).collect()
+    }
+
+    #[test]
+    fn filters() {
+        let groups = groups();
+        let mut diff = StatusDiff::default();
+        let link = |group, path| EntryLink {
+            file: entry(&groups, group, path),
+            group,
+            location: PathBuf::from("/home").join(path),
+        };
+        diff.linked_correctly.push(link("zsh", ".zshrc"));
+        diff.ready_to_link.push(link("zsh", ".zshenv"));
+        diff.linked_correctly.push(link("git", ".gitconfig"));
+
+        let selected = selected_groups(&groups.map, Some("zsh/")).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, "zsh");
+        assert_eq!(selected_groups(&groups.map, None).unwrap().len(), 2);
+        assert_eq!(selected_groups(&groups.map, Some("vim")).unwrap_err().exit_code(), 10);
+
+        let all = ListFilters { tags: vec![], states: vec![] };
+        assert_eq!(matching(&groups, &diff, &all).len(), 3);
+        let work = ListFilters { tags: vec!["work"], states: vec![] };
+        assert_eq!(matching(&groups, &diff, &work), [".gitconfig", ".zshrc"]);
+        let pending = ListFilters { tags: vec![], states: vec![EntryState::Pending] };
+        assert_eq!(matching(&groups, &diff, &pending), [".zshenv"]);
+        let both = ListFilters { tags: vec!["work"], states: vec![EntryState::Pending] };
+        assert!(matching(&groups, &diff, &both).is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use dotao::{
+    error::{DotaoError, DotaoResult},
+    util::{self, to_utf},
+};
+use tsml::FileTree;
+
+// Three-way merge of a single entry, `None` means it's absent, on conflicts ours is kept
+fn merge_entry(
+    base: Option<&FileTree>,
+    ours: Option<&FileTree>,
+    theirs: Option<&FileTree>,
+    conflicts: &mut Vec<PathBuf>,
+) -> Option<FileTree> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    // Both sides changed a directory, merge the entries inside of it if nothing else changed
+    if let (Some(our_dir), Some(their_dir)) = (ours, theirs) {
+        let without_children = |file: &FileTree| match file {
+            FileTree::Directory { path, extra, .. } => {
+                Some(FileTree::new_directory_with_extra(path, vec![], extra.clone()))
+            },
+            _ => None,
+        };
+        let our_shell = without_children(our_dir);
+        if our_shell.is_some() && our_shell == without_children(their_dir) {
+            let base_children = base.and_then(FileTree::children).map(Vec::as_slice);
+            let mut merged = our_dir.clone();
+            // Safe unwrap, both are directories
+            *merged.children_mut().unwrap() = merge_entries(
+                base_children.unwrap_or_default(),
+                our_dir.children().unwrap(),
+                their_dir.children().unwrap(),
+                conflicts,
+            );
+            return Some(merged);
+        }
+    }
+
+    // Added or changed differently, or changed on one side and deleted on the other
+    let path = ours.or(theirs).map(|file| file.path().clone()).unwrap_or_default();
+    conflicts.push(path);
+    ours.cloned()
+}
+
+// Merges each entry by path, in the order of `ours` followed by the new ones of `theirs`
+fn merge_entries(
+    base: &[FileTree],
+    ours: &[FileTree],
+    theirs: &[FileTree],
+    conflicts: &mut Vec<PathBuf>,
+) -> Vec<FileTree> {
+    let paths = ours.iter().chain(theirs).chain(base).map(FileTree::path);
+    let mut seen = BTreeSet::new();
+    let find = |files: &'_ [FileTree], path: &Path| -> Option<FileTree> {
+        files.iter().find(|file| file.path() == path).cloned()
+    };
+
+    let mut merged = vec![];
+    for path in paths {
+        if !seen.insert(path) {
+            continue;
+        }
+        let (base, ours, theirs) = (find(base, path), find(ours, path), find(theirs, path));
+        if let Some(file) = merge_entry(base.as_ref(), ours.as_ref(), theirs.as_ref(), conflicts) {
+            merged.push(file);
+        }
+    }
+    merged
+}
+
+// Three-way merge of a single group, `None` means the group is absent
+fn merge_group(
+    base: Option<&Vec<FileTree>>,
+    ours: Option<&Vec<FileTree>>,
+    theirs: Option<&Vec<FileTree>>,
+    conflicts: &mut Vec<PathBuf>,
+) -> Option<Vec<FileTree>> {
+    if ours == theirs || theirs == base {
+        ours.cloned()
+    } else if ours == base {
+        theirs.cloned()
+    } else if let (None, _) | (_, None) = (ours, theirs) {
+        // Deleted on one side and changed on the other
+        conflicts.push(PathBuf::new());
+        ours.cloned()
+    } else {
+        let [base, ours, theirs] = [base, ours, theirs].map(|group| match group {
+            Some(files) => files.as_slice(),
+            None => &[],
+        });
+        Some(merge_entries(base, ours, theirs, conflicts))
+    }
+}
+
+// Called by git as `dotao merge-driver %O %A %B`, the result is written to `ours_path`
+pub fn run_merge_driver_command(
+    base_path: &str,
+    ours_path: &str,
+    theirs_path: &str,
+) -> DotaoResult<()> {
+    let base = util::load_groups_from_path(base_path)?;
+    let mut ours = util::load_groups_from_path(ours_path)?;
+    let theirs = util::load_groups_from_path(theirs_path)?;
+
+    let group_names: BTreeSet<String> = ours.map.keys().chain(theirs.map.keys()).cloned().collect();
+
+    let mut conflicts = vec![];
+    for name in group_names {
+        let mut group_conflicts = vec![];
+        let merged = merge_group(
+            base.map.get(&name),
+            ours.map.get(&name),
+            theirs.map.get(&name),
+            &mut group_conflicts,
+        );
+        conflicts.extend(group_conflicts.into_iter().map(|path| (name.clone(), path)));
+        match merged {
+            Some(files) => {
+                ours.map.insert(name, files);
+            },
+            None => {
+                ours.map.remove(&name);
+            },
+        }
+    }
+
+    // Don't undo a `dotao migrate` made on the other side, the header holds the version marker
+    if theirs.info.version > ours.info.version {
+        ours.info.version = theirs.info.version;
+        ours.info.file_header = theirs.info.file_header;
+    }
+
+    util::write_groups_to_path(&ours, ours_path)?;
+
+    // Git leaves the file marked as conflicted when the driver fails
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    for (group, path) in &conflicts {
+        match path.as_os_str().is_empty() {
+            true => eprintln!("Conflict in group '{}', deleted on one side.", group),
+            false => eprintln!("Conflict at '{}' of group '{}'.", to_utf(path), group),
+        }
+    }
+    Err(DotaoError::TreeFile {
+        path: PathBuf::from(ours_path),
+        message: format!("{} conflicting change(s), our side was kept", conflicts.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tsml::Tags;
+
+    use super::*;
+
+    fn tagged(path: &str, tag: &str) -> FileTree {
+        let tags = Tags { direct_tags: [tag.to_string()].into(), ..Tags::default() };
+        FileTree::new_regular_with_extra(path, Some(tags))
+    }
+
+    fn merge(
+        base: &[FileTree],
+        ours: &[FileTree],
+        theirs: &[FileTree],
+    ) -> (Vec<FileTree>, Vec<PathBuf>) {
+        let mut conflicts = vec![];
+        let merged = merge_entries(base, ours, theirs, &mut conflicts);
+        (merged, conflicts)
+    }
+
+    #[test]
+    fn add_add() {
+        let base = [FileTree::new_directory(".config", vec![])];
+        let ours = [FileTree::new_directory(".config", vec![FileTree::new_regular(".config/a")])];
+        let theirs = [FileTree::new_directory(".config", vec![FileTree::new_regular(".config/b")])];
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        let expected = FileTree::new_directory(".config", vec![
+            FileTree::new_regular(".config/a"),
+            FileTree::new_regular(".config/b"),
+        ]);
+        assert_eq!(merged, [expected]);
+        assert!(conflicts.is_empty());
+
+        // The same entry added with different tags
+        let (merged, conflicts) = merge(&[], &[tagged("x", "work")], &[tagged("x", "home")]);
+        assert_eq!(merged, [tagged("x", "work")]);
+        assert_eq!(conflicts, [PathBuf::from("x")]);
+    }
+
+    #[test]
+    fn delete_keep() {
+        let base = [FileTree::new_regular("a"), FileTree::new_regular("b")];
+        let ours = [FileTree::new_regular("b")];
+        let theirs = [FileTree::new_regular("a"), FileTree::new_regular("b"), tagged("c", "x")];
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert_eq!(merged, [FileTree::new_regular("b"), tagged("c", "x")]);
+        assert!(conflicts.is_empty());
+
+        // Deleted on their side
+        let (merged, conflicts) = merge(&base, &base, &[FileTree::new_regular("a")]);
+        assert_eq!(merged, [FileTree::new_regular("a")]);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn delete_modify() {
+        let base = [FileTree::new_regular("a"), FileTree::new_regular("b")];
+        let ours = [FileTree::new_regular("b")];
+        let theirs = [tagged("a", "work"), FileTree::new_regular("b")];
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert_eq!(merged, [FileTree::new_regular("b")]);
+        assert_eq!(conflicts, [PathBuf::from("a")]);
+
+        let (merged, conflicts) = merge(&base, &theirs, &ours);
+        assert_eq!(merged, theirs);
+        assert_eq!(conflicts, [PathBuf::from("a")]);
+
+        // A whole group
+        let mut conflicts = vec![];
+        let merged =
+            merge_group(Some(&base.to_vec()), None, Some(&theirs.to_vec()), &mut conflicts);
+        assert_eq!(merged, None);
+        assert_eq!(conflicts.len(), 1);
+    }
+}
@@ -0,0 +1,18 @@
+use dotao::{error::DotaoResult, Engine};
+
+// Rewrite the tree file to the current syntax
+pub fn run_migrate_command(engine: &mut Engine) -> DotaoResult<()> {
+    match engine.migrate_tree_file()? {
+        Some(version) => println!(
+            "Migrated the tree file from syntax version {} to {}, comments now start with '{}'.",
+            version,
+            tsml::CURRENT_VERSION,
+            tsml::comment_prefix(tsml::CURRENT_VERSION)
+        ),
+        None => println!(
+            "The tree file is already at syntax version {}, nothing to migrate.",
+            tsml::CURRENT_VERSION
+        ),
+    }
+    Ok(())
+}
@@ -1,3 +1,18 @@
 pub mod add;
+pub mod apply;
+pub mod export;
+pub mod history;
+pub mod import_chezmoi;
+pub mod import_stow;
 pub mod init;
 pub mod link;
+pub mod list;
+pub mod merge;
+pub mod migrate;
+pub mod mv_group;
+pub mod reabsorb;
+pub mod relink;
+pub mod status;
+pub mod unlink;
+pub mod watch;
+pub mod which;
@@ -0,0 +1,13 @@
+use dotao::{error::DotaoResult, Engine};
+
+// Rename a group, its folder, and relink what pointed into it
+pub fn run_mv_group_command(engine: &mut Engine, old: &str, new: &str) -> DotaoResult<()> {
+    let relinked = engine.move_group(old, new)?;
+    println!(
+        "Renamed group '{}' to '{}', {} link(s) updated.",
+        old.trim_end_matches('/'),
+        new.trim_end_matches('/'),
+        relinked.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+use dotao::{error::DotaoResult, util::to_utf, Engine};
+
+// Move the changes of files that replaced links into the groups, and restore the links
+pub fn run_reabsorb_command(engine: &Engine, locations: &[PathBuf]) -> DotaoResult<()> {
+    let (reabsorbed, skipped) = engine.reabsorb(locations)?;
+    if reabsorbed.is_empty() && skipped.is_empty() {
+        println!("No link was replaced by a file, nothing to reabsorb.");
+        return Ok(());
+    }
+    for entry in reabsorbed.iter() {
+        println!("Reabsorbed '{}' into '{}'.", to_utf(&entry.location), to_utf(&entry.source));
+    }
+    for entry in skipped.iter() {
+        println!(
+            "Skipped '{}', '{}' changed after it, pass its path to reabsorb it anyway.",
+            to_utf(&entry.location),
+            to_utf(&entry.source)
+        );
+    }
+    if !reabsorbed.is_empty() {
+        println!("{} link(s) restored, run `dotao undo` to revert.", reabsorbed.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use dotao::{
+    error::{DotaoError, DotaoResult},
+    util::{ask_for_confirmation, to_utf},
+    Engine, RelinkReason,
+};
+
+// Show the links that lead to their sources through the wrong path, and rewrite them
+pub fn run_relink_command(engine: &Engine, previous: &[PathBuf], yes: bool) -> DotaoResult<()> {
+    let relinks = engine.relinks(previous)?;
+    if relinks.is_empty() {
+        println!("Every link points to the dotfiles folder as configured, nothing to relink.");
+        return Ok(());
+    }
+
+    println!("Links to rewrite:");
+    for relink in relinks.iter() {
+        let reason = match &relink.reason {
+            RelinkReason::Moved(dir) => format!("the dotfiles folder was at '{}'", to_utf(dir)),
+            RelinkReason::Style => "doesn't follow the link style".to_string(),
+        };
+        println!("    '{}' ({})", to_utf(&relink.location), reason);
+        println!("        '{}' -> '{}'", to_utf(&relink.found), to_utf(&relink.target));
+    }
+    if !yes && !ask_for_confirmation("Rewrite them?")? {
+        return Err(DotaoError::Aborted("no link was changed".to_string()));
+    }
+
+    engine.relink(&relinks)?;
+    println!("{} link(s) rewritten.", relinks.len());
+    Ok(())
+}
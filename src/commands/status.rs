@@ -0,0 +1,147 @@
+use std::{fs, path::Path};
+
+use dotao::{
+    diff::line_diff,
+    engine::link_locations,
+    error::{DotaoError, DotaoResult},
+    util::to_utf,
+    Engine,
+};
+use tsml::FileTree;
+
+// Print a titled section with a line for each item, if there are any
+fn section<T>(title: &str, items: &[T], describe: impl Fn(&T) -> String) {
+    if items.is_empty() {
+        return;
+    }
+    println!("{}:", title);
+    for item in items.iter() {
+        println!("    {}", describe(item));
+    }
+}
+
+// Bigger files aren't compared, the diff is quadratic
+const MAX_LINES_TO_COMPARE: usize = 2000;
+
+// Lines changed in the file at `location` compared to `source`, or why they can't be shown
+fn changes_from_source(source: &Path, location: &Path) -> Vec<String> {
+    let read = |path: &Path| fs::read(path).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+    let (before, after) = match (read(source), read(location)) {
+        (Some(before), Some(after)) => (before, after),
+        _ => return vec!["(binary or unreadable, not compared)".to_string()],
+    };
+    if before.lines().count().max(after.lines().count()) > MAX_LINES_TO_COMPARE {
+        return vec!["(too big, not compared)".to_string()];
+    }
+    let changes = line_diff(&before, &after);
+    if changes.is_empty() {
+        return vec!["(same content as the source)".to_string()];
+    }
+    changes
+}
+
+// Report of every entry with active tags, grouped by what needs attention, and of the files missing
+// from the tree file, which fail the check with `fail_untracked`
+pub fn run_status_command(engine: &Engine, fail_untracked: bool) -> DotaoResult<()> {
+    let diff = engine.status()?;
+    let untracked = engine.untracked()?;
+    let source = |file: &FileTree, group: &str| Path::new(group).join(file.path());
+
+    section("Ready to link", &diff.ready_to_link, |link| {
+        format!("'{}' -> '{}'", to_utf(&link.location), to_utf(source(link.file, link.group)))
+    });
+    section("Switching to another variant", &diff.switched_variant, |(link, found)| {
+        let source = source(link.file, link.group);
+        format!("'{}' -> '{}', was '{}'", to_utf(&link.location), to_utf(source), to_utf(found))
+    });
+    section("Left by a variant that no longer matches", &diff.stale_variant, |link| {
+        format!("'{}' -> '{}'", to_utf(&link.location), to_utf(source(link.file, link.group)))
+    });
+    section("Missing from their group folder", &diff.missing_source, |(file, group)| {
+        format!("'{}'", to_utf(source(file, group)))
+    });
+    section("Linked to the wrong place", &diff.linked_incorrectly, |(link, found)| {
+        format!("'{}' -> '{}'", to_utf(&link.location), to_utf(found))
+    });
+    section("Something else in the way", &diff.not_a_symlink, |(link, file_type)| {
+        format!("'{}' is a {}", to_utf(&link.location), file_type)
+    });
+    let replaced = [
+        ("Replaced by a regular file, changes not in the source:", &diff.replaced_by_file),
+        (
+            "Replaced by a regular file, but the source changed after it:",
+            &diff.replaced_by_older_file,
+        ),
+    ];
+    for (title, links) in replaced.iter().filter(|(_, links)| !links.is_empty()) {
+        println!("{}", title);
+        for link in links.iter() {
+            let source = source(link.file, link.group);
+            println!("    '{}' (from '{}'):", to_utf(&link.location), to_utf(&source));
+            for line in changes_from_source(&engine.dotfiles_dir().join(&source), &link.location) {
+                println!("        {}", line);
+            }
+        }
+    }
+    section("Permission denied", &diff.no_permission_to_link_to_target, |link| {
+        format!("'{}'", to_utf(&link.location))
+    });
+    section("Mode changed", &diff.wrong_mode, |(file, group, current)| {
+        // Safe unwrap, only entries with a mode end up in here
+        let expected = file.extra().as_ref().and_then(|tags| tags.mode).unwrap();
+        format!("'{}' is {:04o}, expected {:04o}", to_utf(source(file, group)), current, expected)
+    });
+
+    section("Alternates", &diff.alternates, |alternate| {
+        // Safe index, there's always a variant, and a location for it
+        let variant = alternate.chosen.unwrap_or(alternate.variants[0]);
+        let location = &link_locations(variant, alternate.group, engine.config())[0];
+        match alternate.chosen {
+            Some(chosen) => format!(
+                "'{}' uses '{}' ({})",
+                to_utf(location),
+                to_utf(source(chosen, alternate.group)),
+                alternate.reason
+            ),
+            None => format!("'{}' isn't linked, {}", to_utf(location), alternate.reason),
+        }
+    });
+
+    section("Untracked in group folders", &untracked, |(group, path)| {
+        format!("'{}'", to_utf(Path::new(group).join(path)))
+    });
+
+    println!("{} link(s) in place.", diff.linked_correctly.len());
+    if !diff.stale_variant.is_empty() {
+        println!("Remove the links left by variants by hand, or add a variant that matches.");
+    }
+    if !diff.replaced_by_file.is_empty() {
+        println!("Run `dotao reabsorb` to move the changes into the groups and restore the links.");
+    }
+    if !diff.replaced_by_older_file.is_empty() {
+        println!(
+            "Files older than their source are only reabsorbed by path, `dotao reabsorb <path>`, it \
+             drops the changes of the source."
+        );
+    }
+    if !diff.is_clear() {
+        println!("Run `dotao link` after solving the conflicts above.");
+    } else if !diff.ready_to_link.is_empty()
+        || !diff.switched_variant.is_empty()
+        || !diff.wrong_mode.is_empty()
+    {
+        println!("Run `dotao link` to apply.");
+    }
+    if engine.groups().info.version < tsml::CURRENT_VERSION {
+        println!("The tree file uses an old syntax version, run `dotao migrate` to update it.");
+    }
+
+    if untracked.is_empty() {
+        Ok(())
+    } else if fail_untracked {
+        Err(DotaoError::Untracked { amount: untracked.len() })
+    } else {
+        println!("Run `dotao add <group>` to track the untracked files.");
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+use dotao::{error::DotaoResult, util::to_utf, Engine};
+
+// Remove the links of `groups`, or of every group
+pub fn run_unlink_command(engine: &Engine, groups: &[&str]) -> DotaoResult<()> {
+    let removed = engine.unlink(groups)?;
+    if removed.is_empty() {
+        println!("Nothing is linked, nothing to unlink.");
+        return Ok(());
+    }
+    for location in removed.iter() {
+        println!("Removed the link at '{}'.", to_utf(location));
+    }
+    println!("{} link(s) removed, run `dotao undo` to revert.", removed.len());
+    Ok(())
+}
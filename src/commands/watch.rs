@@ -0,0 +1,355 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::{CString, OsStr},
+    fs, io, mem,
+    os::unix::{ffi::OsStrExt, io::RawFd},
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use dotao::{
+    config::ConflictStrategy,
+    engine::link_locations,
+    error::{DotaoError, DotaoResult},
+    lock::RepoLock,
+    util::{self, to_utf, TREE_FILE_NAME},
+    Engine, Plan,
+};
+
+// Wait for this long without new events before acting on them
+const DEBOUNCE_MILLIS: i32 = 500;
+
+// Structural changes, and writes, to see the tree file being edited
+const WATCH_MASK: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_DELETE_SELF;
+
+// Minimal recursive inotify wrapper
+struct Inotify {
+    fd: RawFd,
+    // Watch descriptor to the directory it watches
+    watches: HashMap<i32, PathBuf>,
+}
+
+impl Inotify {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd, watches: HashMap::new() })
+    }
+
+    fn watch(&mut self, dir: &Path) -> io::Result<()> {
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), WATCH_MASK) };
+        if wd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        self.watches.insert(wd, dir.to_path_buf());
+        Ok(())
+    }
+
+    // Watch `dir` and every directory inside of it, symlinks aren't followed
+    fn watch_recursively(&mut self, dir: &Path) -> io::Result<()> {
+        self.watch(dir)?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                self.watch_recursively(&entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_watching(&self, dir: &Path) -> bool {
+        self.watches.values().any(|watched| watched == dir)
+    }
+
+    // Block for up to `timeout_millis` (-1 is forever), returns the paths and masks of the events,
+    // new directories are watched automatically
+    fn read_events(&mut self, timeout_millis: i32) -> io::Result<Vec<(PathBuf, u32)>> {
+        let mut poll_fd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+        match unsafe { libc::poll(&mut poll_fd, 1, timeout_millis) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                // Interrupted by a signal, act as if nothing happened
+                return if err.kind() == io::ErrorKind::Interrupted {
+                    Ok(vec![])
+                } else {
+                    Err(err)
+                };
+            },
+            0 => return Ok(vec![]),
+            _ => {},
+        }
+
+        // Aligned for `inotify_event`
+        let mut buffer = [0u64; 1024];
+        let read =
+            unsafe { libc::read(self.fd, buffer.as_mut_ptr().cast(), mem::size_of_val(&buffer)) };
+        if read == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let bytes =
+            unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), read as usize) };
+        let header_size = mem::size_of::<libc::inotify_event>();
+        let mut paths = vec![];
+        let mut offset = 0;
+
+        while offset + header_size <= bytes.len() {
+            let event: libc::inotify_event =
+                unsafe { ptr::read_unaligned(bytes[offset..].as_ptr().cast()) };
+            let name_bytes =
+                &bytes[offset + header_size..offset + header_size + event.len as usize];
+            offset += header_size + event.len as usize;
+
+            // The name is padded with nul bytes
+            let name_length =
+                name_bytes.iter().position(|&byte| byte == 0).unwrap_or(name_bytes.len());
+            let name = OsStr::from_bytes(&name_bytes[..name_length]);
+
+            if event.mask & libc::IN_IGNORED != 0 {
+                self.watches.remove(&event.wd);
+                continue;
+            }
+            let dir = match self.watches.get(&event.wd) {
+                Some(dir) => dir.clone(),
+                None => continue,
+            };
+            let path = if name.is_empty() { dir } else { dir.join(name) };
+
+            let is_new_dir = event.mask & libc::IN_ISDIR != 0
+                && event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0;
+            if is_new_dir {
+                // It might be gone already, the next event will tell
+                let _ = self.watch_recursively(&path);
+            }
+            paths.push((path, event.mask));
+        }
+
+        Ok(paths)
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+// Group folders of `engine` that aren't watched yet
+fn watch_groups(inotify: &mut Inotify, engine: &Engine) {
+    for name in engine.groups().map.keys() {
+        let dir = engine.dotfiles_dir().join(name);
+        if !inotify.is_watching(&dir) {
+            if let Err(err) = inotify.watch_recursively(&dir) {
+                eprintln!("Unable to watch group '{}' at '{}': {}.", name, to_utf(&dir), err);
+            }
+        }
+    }
+}
+
+// Entries of a group, by their paths relative to it
+fn entries(engine: &Engine, group: &str) -> BTreeMap<PathBuf, tsml::FileTree> {
+    let files = engine.groups().map.get(group).into_iter().flatten();
+    files
+        .flat_map(|tree| tree.files().skip_dirs(true))
+        .map(|file| (file.path().clone(), file.clone()))
+        .collect()
+}
+
+// Block until there are events, then until none came for `debounce_millis`
+fn read_debounced(inotify: &mut Inotify, debounce_millis: i32) -> io::Result<Vec<(PathBuf, u32)>> {
+    let mut events = inotify.read_events(-1)?;
+    loop {
+        let more = inotify.read_events(debounce_millis)?;
+        if more.is_empty() {
+            return Ok(events);
+        }
+        events.extend(more);
+    }
+}
+
+// Update `groups` in the tree file, then link the new entries that have no conflicts
+fn sync_groups(engine: &mut Engine, groups: &BTreeSet<String>) -> DotaoResult<()> {
+    let before: Vec<BTreeMap<PathBuf, tsml::FileTree>> =
+        groups.iter().map(|group| entries(engine, group)).collect();
+
+    let names: Vec<&str> = groups.iter().map(String::as_str).collect();
+    engine.add_groups(&names)?;
+
+    let mut added = BTreeSet::new();
+    // Where the new entries are linked, with their aliases
+    let mut new_locations = BTreeSet::new();
+    for (group, before) in groups.iter().zip(before) {
+        let after = entries(engine, group);
+        for (path, file) in after.iter().filter(|(path, _)| !before.contains_key(*path)) {
+            println!("Added '{}' to group '{}'.", to_utf(path), group);
+            added.insert((group.clone(), path.clone()));
+            for location in link_locations(file, group, engine.config()) {
+                new_locations.insert((group.clone(), location));
+            }
+        }
+        for (path, file) in before.iter().filter(|(path, _)| !after.contains_key(*path)) {
+            println!("Removed '{}' from group '{}'.", to_utf(path), group);
+            for location in link_locations(file, group, engine.config()) {
+                if fs::symlink_metadata(&location).is_ok() {
+                    println!("    the link at '{}' was left as it is.", to_utf(&location));
+                }
+            }
+        }
+    }
+
+    // Only the new entries, never replacing anything
+    let plan = engine.plan(ConflictStrategy::Skip)?;
+    for conflict in plan.conflicts.iter() {
+        if added.contains(&(conflict.group.clone(), conflict.file.clone())) {
+            eprintln!("Not linking, conflict: {}", conflict);
+        }
+    }
+    // Privileged links need confirmation, `dotao link` asks for it
+    let links: Vec<_> = plan
+        .links
+        .iter()
+        .filter(|link| {
+            new_locations.contains(&(link.group.clone(), link.location.clone())) && !link.privileged
+        })
+        .cloned()
+        .collect();
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    let report = engine.apply(&Plan { links, modes: vec![], conflicts: vec![], ..plan })?;
+    for location in report.linked.iter() {
+        println!("Linked '{}'.", to_utf(location));
+    }
+    Ok(())
+}
+
+// Keep the tree file and links in sync with the group folders, until interrupted, the repository
+// is only locked while syncing
+pub fn run_watch_command(
+    load_engine: impl Fn() -> DotaoResult<Engine>,
+    lock: impl Fn() -> DotaoResult<RepoLock>,
+) -> DotaoResult<()> {
+    let mut engine = load_engine()?;
+    let dotfiles_dir = engine.dotfiles_dir().to_path_buf();
+    let tree_file_path = dotfiles_dir.join(TREE_FILE_NAME);
+    let watch_error = |err| DotaoError::io("watch", &dotfiles_dir, err);
+
+    let mut inotify = Inotify::new().map_err(watch_error)?;
+    // Only the top level, to see changes to the tree file
+    inotify.watch(&dotfiles_dir).map_err(watch_error)?;
+    watch_groups(&mut inotify, &engine);
+
+    println!("Watching '{}' for changes, press Ctrl-C to stop.", to_utf(&dotfiles_dir));
+
+    loop {
+        // Debounce, editors and tools touch several files at once
+        let events = read_debounced(&mut inotify, DEBOUNCE_MILLIS).map_err(watch_error)?;
+
+        // Changed content doesn't matter for groups, only for the tree file
+        let is_structural = |mask: u32| mask & !libc::IN_CLOSE_WRITE != 0;
+
+        let changed_groups: BTreeSet<String> = engine
+            .groups()
+            .map
+            .keys()
+            .filter(|name| {
+                let group_dir = dotfiles_dir.join(name);
+                events.iter().any(|(path, mask)| {
+                    is_structural(*mask)
+                        && path.starts_with(&group_dir)
+                        && !util::is_editor_temporary_file(path)
+                })
+            })
+            .cloned()
+            .collect();
+
+        let tree_file_changed = events.iter().any(|(path, _)| *path == tree_file_path);
+        let result = if !changed_groups.is_empty() || tree_file_changed {
+            lock().and_then(|_lock| {
+                // Edited by hand, or by another command, meanwhile
+                engine = load_engine()?;
+                watch_groups(&mut inotify, &engine);
+                if changed_groups.is_empty() {
+                    Ok(())
+                } else {
+                    sync_groups(&mut engine, &changed_groups)
+                }
+            })
+        } else {
+            Ok(())
+        };
+
+        // Keep watching, the next change might fix it
+        if let Err(err) = result {
+            eprintln!("Error: {}.", err);
+            eprintln!("Hint: {}.", err.hint());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use dotao::config::PartialConfig;
+
+    use super::*;
+
+    #[test]
+    fn events_are_debounced() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut inotify = Inotify::new().unwrap();
+        inotify.watch_recursively(dir.path()).unwrap();
+
+        let path = dir.path().to_path_buf();
+        let writer = thread::spawn(move || {
+            for name in ["a", "b", "c"].iter() {
+                fs::write(path.join(name), "").unwrap();
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let events = read_debounced(&mut inotify, 300).unwrap();
+        writer.join().unwrap();
+
+        for name in ["a", "b", "c"].iter() {
+            assert!(events.iter().any(|(path, _)| *path == dir.path().join(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn syncing_links_new_entries_without_solving_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotfiles_dir = dir.path().join("dotfiles");
+        let group_dir = dotfiles_dir.join("zsh");
+        fs::create_dir_all(&group_dir).unwrap();
+        fs::write(dotfiles_dir.join(TREE_FILE_NAME), "# tsml-version: 2\n- [zsh]\n\".zshrc\"\n")
+            .unwrap();
+        for name in [".zshrc", ".zshenv", ".zprofile"].iter() {
+            fs::write(group_dir.join(name), "").unwrap();
+        }
+        // Blocks the link of `.zprofile`
+        fs::write(dir.path().join(".zprofile"), "mine").unwrap();
+
+        let config = PartialConfig::default().finish(&dotfiles_dir);
+        let mut engine = Engine::new(&dotfiles_dir, config).unwrap();
+        let groups = ["zsh".to_string()].iter().cloned().collect();
+        sync_groups(&mut engine, &groups).unwrap();
+
+        assert_eq!(entries(&engine, "zsh").len(), 3);
+        let zshenv = fs::read_link(dir.path().join(".zshenv")).unwrap();
+        assert_eq!(zshenv, Path::new("dotfiles/zsh/.zshenv"));
+        assert_eq!(fs::read_to_string(dir.path().join(".zprofile")).unwrap(), "mine");
+        // Not new, `dotao link` links it
+        assert!(fs::symlink_metadata(dir.path().join(".zshrc")).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use std::{env, path::Path, process::Command};
+
+use dotao::{
+    config::ConflictStrategy,
+    diff::EntryState,
+    error::{DotaoError, DotaoResult},
+    util::{to_utf, TREE_FILE_NAME},
+    Engine, Owner, Plan,
+};
+
+use super::link::confirm_privileged_commands;
+
+fn find_owner<'a>(engine: &'a Engine, path: &Path) -> DotaoResult<Owner<'a>> {
+    engine.which(path)?.ok_or_else(|| {
+        DotaoError::invalid_usage(
+            format!("'{}' is not provided by any group", to_utf(path)),
+            "run `dotao list --paths` to see every target path",
+        )
+    })
+}
+
+// Show the group, source and declaring line of the entry that provides `path`
+pub fn run_which_command(engine: &Engine, path: &Path) -> DotaoResult<()> {
+    let owner = find_owner(engine, path)?;
+    let state = engine.status()?.state_of(owner.file);
+
+    let tree_file_path = engine.dotfiles_dir().join(TREE_FILE_NAME);
+    println!("group:  {}", owner.group);
+    println!("source: {}", to_utf(&owner.source));
+    println!("target: {} [{}]", to_utf(&owner.location), state);
+    match owner.line {
+        Some(line) => println!("line:   {}:{}", to_utf(&tree_file_path), line),
+        None => println!("line:   {}", to_utf(&tree_file_path)),
+    }
+    Ok(())
+}
+
+// Open the source of `path` in `$EDITOR`, and link it afterwards if the link is missing
+pub fn run_edit_command(engine: &Engine, path: &Path) -> DotaoResult<()> {
+    let owner = find_owner(engine, path)?;
+
+    let editor = env::var("EDITOR").ok().filter(|editor| !editor.is_empty()).ok_or_else(|| {
+        DotaoError::invalid_usage(
+            "the EDITOR env var is not set",
+            "set it to your editor, as in `EDITOR=vim dotao edit <path>`",
+        )
+    })?;
+    // Through `sh`, so `EDITOR` can have arguments, like "code --wait"
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&owner.source)
+        .status()
+        .map_err(|err| DotaoError::Command { command: editor.clone(), reason: err.to_string() })?;
+    if !status.success() {
+        return Err(DotaoError::Command { command: editor, reason: status.to_string() });
+    }
+
+    // The source might have been created, or the link might be missing
+    match engine.status()?.state_of(owner.file) {
+        EntryState::Pending => {
+            let plan = engine.plan(ConflictStrategy::Skip)?;
+            let links = plan.links.into_iter().filter(|link| link.location == owner.location);
+            let plan = Plan { links: links.collect(), modes: vec![], conflicts: vec![], ..plan };
+            confirm_privileged_commands(engine, &plan)?;
+            engine.apply(&plan)?;
+            println!("Linked '{}'.", to_utf(&owner.location));
+        },
+        EntryState::Conflict => {
+            eprintln!(
+                "Can't link '{}', there's a conflict, see `dotao status`.",
+                to_utf(&owner.location)
+            )
+        },
+        _ => {},
+    }
+    Ok(())
+}
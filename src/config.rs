@@ -0,0 +1,503 @@
+//! Typed configuration.
+//!
+//! Sources are loaded in this order, each one overrides the values set by the previous ones:
+//! 1. `$XDG_CONFIG_HOME/dotao/config.toml` (defaults to `~/.config/dotao/config.toml`).
+//! 2. `config.toml` inside of the dotfiles folder (repo-local config).
+//! 3. Env vars: `DOTAO_TARGET_ROOT`, `DOTAO_LINK_STYLE`, `DOTAO_TAGS` and
+//!    `DOTAO_CONFLICT_STRATEGY`.
+//! 4. CLI flags: `--target-root`, `--link-style`, `--tags` and `--conflict`.
+//!
+//! Config file example, with the default values:
+//! ```toml
+//! target_root = ".."          # Where links are created, relative to the dotfiles folder
+//! link_style = "relative"     # "relative" or "absolute" link targets
+//! default_tags = []           # Active tags, tagged entries are skipped unless all their tags are
+//! conflict_strategy = "abort" # "abort", "skip" or "overwrite"
+//! system_groups = []          # Groups linked to absolute paths, with `/` as their target root
+//! escalation_command = "sudo" # Runs the operations on system groups that need privileges
+//!
+//! [hooks]
+//! pre_link = []               # Shell commands to run before linking
+//! post_link = []              # Shell commands to run after linking
+//! ```
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    error::{DotaoError, DotaoResult},
+    util::{self, to_utf},
+};
+
+const TOP_LEVEL_KEYS: &[&str] = &[
+    "target_root",
+    "link_style",
+    "default_tags",
+    "conflict_strategy",
+    "system_groups",
+    "escalation_command",
+    "hooks",
+];
+const HOOKS_KEYS: &[&str] = &["pre_link", "post_link"];
+
+/// How the text of created symlinks is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// `../dotfiles/group/file`
+    Relative,
+    /// `/home/user/dotfiles/group/file`
+    Absolute,
+}
+
+/// What to do when something already exists at the place of a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Don't link anything if there's any conflict.
+    Abort,
+    /// Link what's possible, leave conflicting paths untouched.
+    Skip,
+    /// Replace conflicting files and symlinks (never directories).
+    Overwrite,
+}
+
+impl FromStr for LinkStyle {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "relative" => Ok(Self::Relative),
+            "absolute" => Ok(Self::Absolute),
+            _ => Err(r#"one of "relative" or "absolute""#.to_string()),
+        }
+    }
+}
+
+impl FromStr for ConflictStrategy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "abort" => Ok(Self::Abort),
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            _ => Err(r#"one of "abort", "skip" or "overwrite""#.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ConflictStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Abort => write!(f, "abort"),
+            Self::Skip => write!(f, "skip"),
+            Self::Overwrite => write!(f, "overwrite"),
+        }
+    }
+}
+
+/// Shell commands run around linking, inside of the dotfiles folder.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    pub pre_link: Vec<String>,
+    pub post_link: Vec<String>,
+}
+
+/// Final configuration, after every source was loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub target_root: PathBuf,
+    pub link_style: LinkStyle,
+    pub default_tags: Vec<String>,
+    pub conflict_strategy: ConflictStrategy,
+    /// Groups whose entries are absolute paths, as in `etc/hosts` for `/etc/hosts`.
+    pub system_groups: Vec<String>,
+    /// Program (and arguments) that runs the operations on system groups that need privileges.
+    pub escalation_command: String,
+    pub hooks: Hooks,
+}
+
+/// A single configuration source, `None` values are left for other sources to decide.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartialConfig {
+    pub target_root: Option<PathBuf>,
+    pub link_style: Option<LinkStyle>,
+    pub default_tags: Option<Vec<String>>,
+    pub conflict_strategy: Option<ConflictStrategy>,
+    pub system_groups: Option<Vec<String>>,
+    pub escalation_command: Option<String>,
+    pub pre_link: Option<Vec<String>>,
+    pub post_link: Option<Vec<String>>,
+}
+
+fn toml_type_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Array(..) => "Array",
+        toml::Value::Boolean(..) => "Boolean",
+        toml::Value::Datetime(..) => "Datetime",
+        toml::Value::Float(..) => "Float",
+        toml::Value::Integer(..) => "Integer",
+        toml::Value::String(..) => "String",
+        toml::Value::Table(..) => "Table",
+    }
+    .to_string()
+}
+
+fn wrong_type_error(key: &str, source: &str, expected: &str, value: &toml::Value) -> DotaoError {
+    DotaoError::Config(format!(
+        "'{}' variable at '{}' should be of type {}, instead, it's of type {}",
+        key,
+        source,
+        expected,
+        toml_type_to_string(value)
+    ))
+}
+
+fn unknown_key_error(key: &str, source: &str, expected: &[&str]) -> DotaoError {
+    DotaoError::Config(format!(
+        "unknown variable '{}' at '{}', expected one of: {}",
+        key,
+        source,
+        expected.join(", ")
+    ))
+}
+
+fn expect_str<'a>(key: &str, source: &str, value: &'a toml::Value) -> DotaoResult<&'a str> {
+    value.as_str().ok_or_else(|| wrong_type_error(key, source, "String", value))
+}
+
+fn expect_string_array(key: &str, source: &str, value: &toml::Value) -> DotaoResult<Vec<String>> {
+    let wrong_type = || wrong_type_error(key, source, "Array of String", value);
+    let array = value.as_array().ok_or_else(wrong_type)?;
+    array.iter().map(|item| item.as_str().map(String::from).ok_or_else(wrong_type)).collect()
+}
+
+fn parse_value<T: FromStr<Err = String>>(key: &str, source: &str, text: &str) -> DotaoResult<T> {
+    text.parse().map_err(|expected| {
+        DotaoError::Config(format!(
+            "'{}' variable at '{}' should be {}, instead, it's \"{}\"",
+            key, source, expected, text
+        ))
+    })
+}
+
+// Comma separated list, used by env vars and CLI flags
+fn split_tags(text: &str) -> Vec<String> {
+    text.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(String::from).collect()
+}
+
+impl PartialConfig {
+    /// Parse and validate a config file content, `source` is used in error messages, and
+    /// relative paths are resolved against `base_dir`.
+    pub fn from_toml_text(text: &str, source: &str, base_dir: &Path) -> DotaoResult<Self> {
+        let value = text.parse::<toml::Value>().map_err(|err| {
+            DotaoError::Config(format!("failed to parse toml at '{}': {}", source, err))
+        })?;
+        // Safe unwrap, a parsed document is always a table
+        let table = value.as_table().unwrap();
+
+        let mut config = Self::default();
+        let mut deprecated_target_root = None;
+        for (key, value) in table {
+            match key.as_str() {
+                // What older versions called the folder links are created in
+                "backup_dir" => {
+                    let path = expect_str(key, source, value)?;
+                    eprintln!(
+                        "Warning: 'backup_dir' variable at '{}' is deprecated, use 'target_root' \
+                         instead.",
+                        source
+                    );
+                    deprecated_target_root = Some(base_dir.join(util::expand_tilde(path)?));
+                },
+                "target_root" => {
+                    let path = expect_str(key, source, value)?;
+                    config.target_root = Some(base_dir.join(util::expand_tilde(path)?));
+                },
+                "link_style" => {
+                    let text = expect_str(key, source, value)?;
+                    config.link_style = Some(parse_value(key, source, text)?);
+                },
+                "default_tags" => {
+                    config.default_tags = Some(expect_string_array(key, source, value)?);
+                },
+                "conflict_strategy" => {
+                    let text = expect_str(key, source, value)?;
+                    config.conflict_strategy = Some(parse_value(key, source, text)?);
+                },
+                "system_groups" => {
+                    config.system_groups = Some(expect_string_array(key, source, value)?);
+                },
+                "escalation_command" => {
+                    let command = expect_str(key, source, value)?;
+                    if command.trim().is_empty() {
+                        return Err(DotaoError::Config(format!(
+                            "'{}' variable at '{}' can't be empty",
+                            key, source
+                        )));
+                    }
+                    config.escalation_command = Some(command.to_string());
+                },
+                "hooks" => {
+                    let hooks = value
+                        .as_table()
+                        .ok_or_else(|| wrong_type_error(key, source, "Table", value))?;
+                    for (hook_key, value) in hooks {
+                        let full_key = format!("hooks.{}", hook_key);
+                        let commands = match hook_key.as_str() {
+                            "pre_link" => &mut config.pre_link,
+                            "post_link" => &mut config.post_link,
+                            _ => return Err(unknown_key_error(&full_key, source, HOOKS_KEYS)),
+                        };
+                        *commands = Some(expect_string_array(&full_key, source, value)?);
+                    }
+                },
+                _ => return Err(unknown_key_error(key, source, TOP_LEVEL_KEYS)),
+            }
+        }
+        if config.target_root.is_none() {
+            config.target_root = deprecated_target_root;
+        }
+        Ok(config)
+    }
+
+    /// Load from file at `path`, if it exists, relative paths in it are resolved against the
+    /// folder of the file.
+    pub fn from_toml_file(path: &Path) -> DotaoResult<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|source| DotaoError::io("read config file", path, source))?;
+        // Safe unwrap, it's a file
+        let base_dir = path.parent().unwrap();
+        Self::from_toml_text(&text, &to_utf(path), base_dir)
+    }
+
+    /// Load from `DOTAO_*` env vars.
+    pub fn from_env() -> DotaoResult<Self> {
+        fn var(name: &str) -> Option<String> {
+            env::var(name).ok().filter(|value| !value.is_empty())
+        }
+        fn parse_var<T: FromStr<Err = String>>(name: &str, key: &str) -> DotaoResult<Option<T>> {
+            var(name).map(|text| parse_value(key, &format!("${}", name), &text)).transpose()
+        }
+
+        let target_root = match var("DOTAO_TARGET_ROOT") {
+            Some(path) => Some(util::current_dir()?.join(util::expand_tilde(&path)?)),
+            None => None,
+        };
+
+        Ok(Self {
+            target_root,
+            link_style: parse_var("DOTAO_LINK_STYLE", "link_style")?,
+            default_tags: var("DOTAO_TAGS").map(|text| split_tags(&text)),
+            conflict_strategy: parse_var("DOTAO_CONFLICT_STRATEGY", "conflict_strategy")?,
+            system_groups: None,
+            escalation_command: None,
+            pre_link: None,
+            post_link: None,
+        })
+    }
+
+    /// Load from the global CLI flags.
+    pub fn from_args(args: &clap::ArgMatches) -> DotaoResult<Self> {
+        fn parse_flag<T: FromStr<Err = String>>(
+            args: &clap::ArgMatches,
+            flag: &str,
+            key: &str,
+        ) -> DotaoResult<Option<T>> {
+            args.value_of(flag)
+                .map(|text| parse_value(key, &format!("--{}", flag), text))
+                .transpose()
+        }
+
+        let target_root = match args.value_of("target-root") {
+            Some(path) => Some(util::current_dir()?.join(path)),
+            None => None,
+        };
+
+        Ok(Self {
+            target_root,
+            link_style: parse_flag(args, "link-style", "link_style")?,
+            default_tags: args.value_of("tags").map(split_tags),
+            conflict_strategy: parse_flag(args, "conflict", "conflict_strategy")?,
+            system_groups: None,
+            escalation_command: None,
+            pre_link: None,
+            post_link: None,
+        })
+    }
+
+    /// Override the values of `self` with the ones set in `other`.
+    pub fn merge(&mut self, other: Self) {
+        fn override_with<T>(value: &mut Option<T>, other: Option<T>) {
+            if other.is_some() {
+                *value = other;
+            }
+        }
+        override_with(&mut self.target_root, other.target_root);
+        override_with(&mut self.link_style, other.link_style);
+        override_with(&mut self.default_tags, other.default_tags);
+        override_with(&mut self.conflict_strategy, other.conflict_strategy);
+        override_with(&mut self.system_groups, other.system_groups);
+        override_with(&mut self.escalation_command, other.escalation_command);
+        override_with(&mut self.pre_link, other.pre_link);
+        override_with(&mut self.post_link, other.post_link);
+    }
+
+    /// Fill unset values with the defaults.
+    pub fn finish(self, dotfiles_dir: &Path) -> Config {
+        let target_root = self.target_root.unwrap_or_else(|| dotfiles_dir.join(".."));
+        Config {
+            target_root: util::normalize_path(&target_root),
+            link_style: self.link_style.unwrap_or(LinkStyle::Relative),
+            default_tags: self.default_tags.unwrap_or_default(),
+            conflict_strategy: self.conflict_strategy.unwrap_or(ConflictStrategy::Abort),
+            system_groups: self.system_groups.unwrap_or_default(),
+            escalation_command: self.escalation_command.unwrap_or_else(|| "sudo".to_string()),
+            hooks: Hooks {
+                pre_link: self.pre_link.unwrap_or_default(),
+                post_link: self.post_link.unwrap_or_default(),
+            },
+        }
+    }
+}
+
+// `$XDG_CONFIG_HOME/dotao/config.toml`, or `~/.config/dotao/config.toml`
+pub fn user_config_location() -> DotaoResult<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => util::home_dir()?.join(".config"),
+    };
+    Ok(config_dir.join("dotao/config.toml"))
+}
+
+// Repo-local config, inside of the dotfiles folder
+pub fn local_config_location(dotfiles_dir: &Path) -> PathBuf {
+    dotfiles_dir.join("config.toml")
+}
+
+impl Config {
+    /// If `group` is a system group, linked to absolute paths.
+    pub fn is_system_group(&self, group: &str) -> bool {
+        self.system_groups.iter().any(|system_group| system_group == group)
+    }
+
+    /// Where the links of `group` are created, `/` for system groups.
+    pub fn target_root_of(&self, group: &str) -> &Path {
+        if self.is_system_group(group) {
+            Path::new("/")
+        } else {
+            &self.target_root
+        }
+    }
+
+    /// Load every source in order, `cli` being the last one.
+    pub fn load(dotfiles_dir: &Path, cli: PartialConfig) -> DotaoResult<Self> {
+        let mut config = PartialConfig::from_toml_file(&user_config_location()?)?;
+        config.merge(PartialConfig::from_toml_file(&local_config_location(dotfiles_dir))?);
+        config.merge(PartialConfig::from_env()?);
+        config.merge(cli);
+        Ok(config.finish(dotfiles_dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> DotaoResult<PartialConfig> {
+        PartialConfig::from_toml_text(text, "config.toml", Path::new("/home/user/dotfiles"))
+    }
+
+    #[test]
+    fn test_parse_and_defaults() {
+        let config = parse(indoc::indoc!(
+            r#"
+            target_root = "../target"
+            link_style = "absolute"
+            default_tags = ["linux", "laptop"]
+            system_groups = ["etc"]
+            escalation_command = "doas"
+
+            [hooks]
+            post_link = ["echo done"]
+            "#
+        ))
+        .unwrap()
+        .finish(Path::new("/home/user/dotfiles"));
+
+        assert_eq!(config, Config {
+            target_root: PathBuf::from("/home/user/target"),
+            link_style: LinkStyle::Absolute,
+            default_tags: vec!["linux".to_string(), "laptop".to_string()],
+            conflict_strategy: ConflictStrategy::Abort,
+            system_groups: vec!["etc".to_string()],
+            escalation_command: "doas".to_string(),
+            hooks: Hooks { pre_link: vec![], post_link: vec!["echo done".to_string()] },
+        });
+
+        let defaults = PartialConfig::default().finish(Path::new("/home/user/dotfiles"));
+        assert_eq!(defaults.target_root, PathBuf::from("/home/user"));
+        assert_eq!(defaults.link_style, LinkStyle::Relative);
+        assert_eq!(defaults.escalation_command, "sudo");
+        assert_eq!(config.target_root_of("etc"), Path::new("/"));
+        assert_eq!(config.target_root_of("zsh"), Path::new("/home/user/target"));
+    }
+
+    #[test]
+    fn test_paths_relative_to_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_config = dir.path().join(".config/dotao/config.toml");
+        fs::create_dir_all(user_config.parent().unwrap()).unwrap();
+        fs::write(&user_config, r#"target_root = "../../target""#).unwrap();
+
+        let config = PartialConfig::from_toml_file(&user_config).unwrap();
+        let config = config.finish(&dir.path().join("dotfiles"));
+        assert_eq!(config.target_root, dir.path().join("target"));
+    }
+
+    #[test]
+    fn test_later_sources_override() {
+        let mut config = parse(r#"conflict_strategy = "skip""#).unwrap();
+        config.merge(parse(r#"link_style = "absolute""#).unwrap());
+        config.merge(parse(r#"conflict_strategy = "overwrite""#).unwrap());
+
+        assert_eq!(config.link_style, Some(LinkStyle::Absolute));
+        assert_eq!(config.conflict_strategy, Some(ConflictStrategy::Overwrite));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let message = |text| parse(text).unwrap_err().to_string();
+
+        assert_eq!(
+            message("link_style = 1"),
+            "'link_style' variable at 'config.toml' should be of type String, instead, it's of \
+             type Integer"
+        );
+        assert_eq!(
+            message("default_tags = [1]"),
+            "'default_tags' variable at 'config.toml' should be of type Array of String, \
+             instead, it's of type Array"
+        );
+        assert_eq!(
+            message("[hooks]\npre_commit = []"),
+            "unknown variable 'hooks.pre_commit' at 'config.toml', expected one of: pre_link, \
+             post_link"
+        );
+        assert!(parse(r#"conflict_strategy = "yolo""#).is_err());
+    }
+
+    #[test]
+    fn test_deprecated_backup_dir() {
+        let config = parse("backup_dir = '..'").unwrap();
+        assert_eq!(config.target_root, Some(PathBuf::from("/home/user/dotfiles/..")));
+
+        let config = parse("backup_dir = '..'\ntarget_root = '/mnt/home'").unwrap();
+        assert_eq!(config.target_root, Some(PathBuf::from("/mnt/home")));
+    }
+}
@@ -1,26 +1,166 @@
 use std::{
-    fs, io,
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
     path::{Path, PathBuf},
+    ptr,
+    str::FromStr,
 };
 
 use file_type_enum::FileType as FileTypeEnum;
+use permissions::PermissionBits;
 use tsml::FileTree;
 
 use crate::{
-    error,
-    util::{self, to_utf},
+    alternates::{self, Alternate, Facts},
+    config::Config,
+    engine::{construct_link_target, link_locations},
+    error::{DotaoError, DotaoResult},
+    util,
 };
 
+// If both paths lead to the same file, after following symlinks
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// If the file at `a` was modified after the one at `b`
+fn is_newer(a: &Path, b: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(a), modified(b)) {
+        (Ok(a), Ok(b)) => a > b,
+        _ => false,
+    }
+}
+
+// Tagged entries are only used if all of their tags are active
+pub fn has_active_tags(file: &FileTree, active_tags: &[String]) -> bool {
+    file.extra()
+        .as_ref()
+        .is_none_or(|tags| tags.effective_tags().into_iter().all(|tag| active_tags.contains(tag)))
+}
+
+/// Files inside of the folder of each group that are missing from the tree file, by group, with
+/// paths relative to the group folder. Ignored files (see [`util::is_ignored`]) are left out, like
+/// `add` does.
+///
+/// Entries cover everything inside of them, so the files of a linked directory aren't reported.
+pub fn untracked_files<'a>(
+    map: &'a tsml::GroupsMap,
+    dotfiles_dir: &Path,
+) -> DotaoResult<Vec<(&'a str, PathBuf)>> {
+    let mut untracked = vec![];
+
+    for (group_name, trees) in map.iter() {
+        let group_dir = dotfiles_dir.join(group_name);
+        // Reported by its entries, as missing sources
+        if !group_dir.is_dir() {
+            continue;
+        }
+        let tracked: HashSet<&Path> = trees
+            .iter()
+            .flat_map(|tree| tree.files().skip_dirs(true))
+            .map(|file| file.path().as_path())
+            .collect();
+
+        // Symlinks are entries, like `add` collects them
+        let collected = FileTree::collect_from_directory_symlink(&group_dir)
+            .map_err(|source| DotaoError::FileTree { operation: "read group", source })?;
+        for file in collected.iter().flat_map(|tree| tree.files().skip_dirs(true)) {
+            let relative = file.path().strip_prefix(&group_dir).unwrap_or(file.path());
+            let is_tracked = relative.ancestors().any(|path| tracked.contains(path));
+            if !is_tracked && !util::is_ignored(relative) {
+                untracked.push((group_name.as_str(), relative.to_path_buf()));
+            }
+        }
+    }
+    Ok(untracked)
+}
+
+/// Live state of a single entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryState {
+    /// Linked to the right source.
+    Linked,
+    /// Nothing at the target, ready to be linked.
+    Pending,
+    /// Something else at the target, or it can't be inspected.
+    Conflict,
+    /// The source is missing from the group folder.
+    Missing,
+    /// Left out, not all of its tags are active.
+    Inactive,
+}
+
+impl FromStr for EntryState {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "linked" => Ok(Self::Linked),
+            "pending" => Ok(Self::Pending),
+            "conflict" => Ok(Self::Conflict),
+            "missing" => Ok(Self::Missing),
+            "inactive" => Ok(Self::Inactive),
+            _ => {
+                Err(r#"one of "linked", "pending", "conflict", "missing" or "inactive""#
+                    .to_string())
+            },
+        }
+    }
+}
+
+impl fmt::Display for EntryState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Linked => write!(f, "linked"),
+            Self::Pending => write!(f, "pending"),
+            Self::Conflict => write!(f, "conflict"),
+            Self::Missing => write!(f, "missing"),
+            Self::Inactive => write!(f, "inactive"),
+        }
+    }
+}
+
+/// A link of an entry, entries have one at their path inside of the target root, and one more
+/// for each of their aliases.
+#[derive(Debug, Clone)]
+pub struct EntryLink<'a> {
+    pub file: &'a FileTree,
+    pub group: &'a str,
+    /// Where the link goes.
+    pub location: PathBuf,
+}
+
+/// State of each entry of the tree file, entries are paired with the name of their group, links
+/// have a state of their own.
 #[derive(Debug, Default, Clone)]
 pub struct StatusDiff<'a> {
     // Ok stuff
-    pub linked_correctly: Vec<&'a FileTree>,
-    pub ready_to_link: Vec<(&'a FileTree, PathBuf)>,
+    pub linked_correctly: Vec<EntryLink<'a>>,
+    pub ready_to_link: Vec<EntryLink<'a>>,
+    // Links to another variant of the same alternate, the chosen one changed, with what they
+    // point to
+    pub switched_variant: Vec<(EntryLink<'a>, PathBuf)>,
+    // Links of a variant left behind by alternates that have no match anymore, by the variant
+    pub stale_variant: Vec<EntryLink<'a>>,
     // Error stuff
-    pub missing_source: Vec<&'a FileTree>,
-    pub linked_incorrectly: Vec<(&'a FileTree, PathBuf)>,
-    pub not_a_symlink: Vec<(&'a FileTree, FileTypeEnum)>,
-    pub no_permission_to_link_to_target: Vec<&'a FileTree>,
+    pub missing_source: Vec<(&'a FileTree, &'a str)>,
+    pub linked_incorrectly: Vec<(EntryLink<'a>, PathBuf)>,
+    pub not_a_symlink: Vec<(EntryLink<'a>, FileTypeEnum)>,
+    // Regular files where dotao had created the link, applications that save by renaming a new
+    // file over the old one do that, the changes are in there instead of in the source
+    pub replaced_by_file: Vec<EntryLink<'a>>,
+    // Like `replaced_by_file`, but the source was modified after the file (edited, or pulled),
+    // reabsorbing it would drop those changes
+    pub replaced_by_older_file: Vec<EntryLink<'a>>,
+    pub no_permission_to_link_to_target: Vec<EntryLink<'a>>,
+    // Sources whose mode differs from the one in the tree file, with the current mode
+    pub wrong_mode: Vec<(&'a FileTree, &'a str, PermissionBits)>,
+    // Variants of each alternate, only the chosen ones are in the lists above
+    pub alternates: Vec<Alternate<'a>>,
 }
 
 impl<'a> StatusDiff<'a> {
@@ -28,98 +168,290 @@ impl<'a> StatusDiff<'a> {
         Self::default()
     }
 
-    pub fn from_groups_map(map: &'a tsml::GroupsMap) -> Self {
+    // Group and source paths are resolved against `dotfiles_dir`, entries with inactive tags
+    // are left out, and so are the variants of alternates not chosen with `facts`,
+    // `recorded_links` are the links dotao created, by location (see `history::recorded_links`)
+    pub fn from_groups_map(
+        map: &'a tsml::GroupsMap,
+        dotfiles_dir: &Path,
+        config: &Config,
+        facts: &Facts,
+        recorded_links: &HashMap<PathBuf, PathBuf>,
+    ) -> DotaoResult<Self> {
         let mut diff = Self::new();
+        diff.alternates = alternates::alternates(map, &config.default_tags, facts);
 
-        let vecs = map.values().flat_map(|x| x.iter());
-        let groups = map.keys();
-        let zipped = vecs.zip(groups);
-        // let zipped_trees = zipped_tree_vecs
-        // .flat_map(|(vecs, groups)| vecs.files().skip_dirs(true).map(move |x| (x, groups)));
+        let trees = map.iter().flat_map(|(group_name, trees)| {
+            trees.iter().map(move |tree| (tree, group_name.as_str()))
+        });
 
-        for (tree, group_name) in zipped {
+        for (tree, group_name) in trees {
             for file in tree.files().skip_dirs(true) {
-                if !file.is_regular() {
-                    error!("Your config contains a symlink syntax that is not yet supported, to the file '{}'.", to_utf(file.path()));
+                if !has_active_tags(file, &config.default_tags)
+                    || diff.alternates.iter().any(|alternate| alternate.leaves_out(file))
+                {
+                    continue;
                 }
 
-                let source_location = Path::new(group_name).join(file.path());
-                // dbg!(&source_location);
-                if !source_location.exists() {
-                    diff.missing_source.push(file);
+                let source_location = dotfiles_dir.join(group_name).join(file.path());
+                // Symlink entries are symlinks inside of the group folder, they are linked to like
+                // any other source, even if they point to nothing
+                let source_exists = if file.is_symlink() {
+                    fs::symlink_metadata(&source_location).is_ok()
+                } else {
+                    source_location.exists()
+                };
+                if !source_exists {
+                    diff.missing_source.push((file, group_name));
                     continue;
                 }
 
-                let backup_dir = util::backup_dir();
-                let destination_location = backup_dir.join(file.path());
-                if !destination_location.exists() {
-                    diff.ready_to_link.push((file, PathBuf::from(group_name)));
-                    continue;
+                // Independent from the link, the source itself drifted
+                if let Some(expected) = file.extra().as_ref().and_then(|tags| tags.mode) {
+                    let metadata = fs::metadata(&source_location)
+                        .map_err(|err| DotaoError::io("read metadata of", &source_location, err))?;
+                    let current = PermissionBits::from(metadata.permissions());
+                    if current != expected {
+                        diff.wrong_mode.push((file, group_name, current));
+                    }
                 }
 
-                // Gather file_type, treat ErrorKind::PermissionDenied, or exit
-                let file_type = match FileTypeEnum::from_symlink_path(&destination_location) {
-                    Ok(file_type) => file_type,
-                    Err(err) => {
-                        if matches!(err.kind(), io::ErrorKind::PermissionDenied) {
-                            diff.no_permission_to_link_to_target.push(file);
-                            continue;
-                        } else {
-                            error!(
-                                "Error: interrupted while scanning HOME searching for file at '{}': {}.",
-                                to_utf(destination_location),
-                                err
-                            );
-                        }
-                    },
-                };
+                for location in link_locations(file, group_name, config) {
+                    let link = EntryLink { file, group: group_name, location };
+                    diff.add_link(link, &source_location, dotfiles_dir, config, recorded_links)?;
+                }
+            }
+        }
+
+        let unmatched = diff.alternates.iter().filter(|alternate| alternate.chosen.is_none());
+        let variants: Vec<_> = unmatched
+            .flat_map(|alternate| {
+                alternate.variants.iter().map(move |file| (*file, alternate.group))
+            })
+            .collect();
+        for (file, group) in variants {
+            for location in link_locations(file, group, config) {
+                let target = construct_link_target(file, group, &location, dotfiles_dir, config);
+                let is_stale = fs::read_link(&location).is_ok_and(|found| found == target);
+                if is_stale && !diff.stale_variant.iter().any(|link| link.location == location) {
+                    diff.stale_variant.push(EntryLink { file, group, location });
+                }
+            }
+        }
+        Ok(diff)
+    }
 
-                let _target_path = match file_type {
-                    FileTypeEnum::Symlink => {
-                        fs::read_link(&destination_location)
-                            .unwrap_or_else(|err| error!("asjdaskdasjdn: {}.", err));
-                    },
-                    _ => {
-                        diff.not_a_symlink.push((file, file_type));
-                        continue;
-                    },
+    // Check the link of an entry at `link.location`, pointing to `source_location`
+    fn add_link(
+        &mut self,
+        link: EntryLink<'a>,
+        source_location: &Path,
+        dotfiles_dir: &Path,
+        config: &Config,
+        recorded_links: &HashMap<PathBuf, PathBuf>,
+    ) -> DotaoResult<()> {
+        let destination_location = &link.location;
+        // Don't follow, a broken symlink is still in the way
+        if fs::symlink_metadata(destination_location).is_err() {
+            self.ready_to_link.push(link);
+            return Ok(());
+        }
+
+        // Gather file_type, treat ErrorKind::PermissionDenied, or fail
+        let file_type = match FileTypeEnum::from_symlink_path(destination_location) {
+            Ok(file_type) => file_type,
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                self.no_permission_to_link_to_target.push(link);
+                return Ok(());
+            },
+            Err(err) => return Err(DotaoError::io("scan", destination_location, err)),
+        };
+
+        match file_type {
+            FileTypeEnum::Symlink => {
+                let link_target = fs::read_link(destination_location)
+                    .map_err(|err| DotaoError::io("read symlink", destination_location, err))?;
+                // If it's pointing to the right place
+                let expected_target = construct_link_target(
+                    link.file,
+                    link.group,
+                    destination_location,
+                    dotfiles_dir,
+                    config,
+                );
+                let is_other_variant = || {
+                    let alternate = self.alternates.iter().find(|alternate| {
+                        alternate.chosen.is_some_and(|chosen| ptr::eq(chosen, link.file))
+                    });
+                    alternate.into_iter().flat_map(|alternate| &alternate.variants).any(|variant| {
+                        link_target
+                            == construct_link_target(
+                                variant,
+                                link.group,
+                                destination_location,
+                                dotfiles_dir,
+                                config,
+                            )
+                    })
                 };
+                if link_target == expected_target {
+                    self.linked_correctly.push(link);
+                } else if is_other_variant() {
+                    self.switched_variant.push((link, link_target));
+                } else {
+                    self.linked_incorrectly.push((link, link_target));
+                }
+            },
+            // Reached through a symlinked parent directory that leads to the source, like the
+            // folded directories of Stow
+            _ if is_same_file(destination_location, source_location) => {
+                self.linked_correctly.push(link);
+            },
+            FileTypeEnum::Regular
+                if !link.file.is_symlink()
+                    && source_location.is_file()
+                    && recorded_links.contains_key(destination_location) =>
+            {
+                if is_newer(source_location, destination_location) {
+                    self.replaced_by_older_file.push(link);
+                } else {
+                    self.replaced_by_file.push(link);
+                }
+            },
+            _ => {
+                self.not_a_symlink.push((link, file_type));
+            },
+        };
+        Ok(())
+    }
 
-                //
-                //
-                //
-            }
+    /// State of `file`, which must be an entry of the map this diff was created from.
+    pub fn state_of(&self, file: &FileTree) -> EntryState {
+        let is_file = |other: &&FileTree| ptr::eq(*other, file);
+        let is_link_of_file = |link: &EntryLink| ptr::eq(link.file, file);
+
+        // Entries with aliases have many links, the worst state wins
+        if self.missing_source.iter().map(|(other, _)| other).any(is_file) {
+            EntryState::Missing
+        } else if self.linked_incorrectly.iter().map(|(link, _)| link).any(is_link_of_file)
+            || self.not_a_symlink.iter().map(|(link, _)| link).any(is_link_of_file)
+            || self.replaced_by_file.iter().any(is_link_of_file)
+            || self.replaced_by_older_file.iter().any(is_link_of_file)
+            || self.no_permission_to_link_to_target.iter().any(is_link_of_file)
+        {
+            EntryState::Conflict
+        } else if self.ready_to_link.iter().any(is_link_of_file)
+            || self.switched_variant.iter().map(|(link, _)| link).any(is_link_of_file)
+        {
+            EntryState::Pending
+        } else if self.linked_correctly.iter().any(is_link_of_file) {
+            EntryState::Linked
+        } else {
+            EntryState::Inactive
         }
-        //     // If it is error, treat
-
-        //     // Match on success of FileTypeEnum::from_symlink_path query
-        //     match file_type {
-        //         FileTypeEnum::Symlink => {
-        //             let link_target = fs::read_link(&dotfiles_path).unwrap_or_else(|err| {
-        //                 error!(
-        //                     "Error while trying to read symlink at '{}': {}.",
-        //                     to_utf(&dotfiles_path),
-        //                     err
-        //                 )
-        //             });
-        //             // If it's pointing to the right place
-        //             if link_target == expected_path {
-        //                 diff.linked_correctly.push(file);
-        //             } else {
-        //                 diff.linked_incorrectly.push((file, link_target));
-        //             }
-        //         },
-        //         _ => diff.different_file_type_at_location.push((file, file_type)),
-        //     }
-        // }
-        // dbg!(&diff);
-        diff
     }
 
     pub fn is_clear(&self) -> bool {
         self.missing_source.is_empty()
             && self.linked_incorrectly.is_empty()
             && self.not_a_symlink.is_empty()
+            && self.replaced_by_file.is_empty()
+            && self.replaced_by_older_file.is_empty()
             && self.no_permission_to_link_to_target.is_empty()
     }
 }
+
+/// Lines removed from `before` (prefixed by `-`) and added in `after` (prefixed by `+`), in order,
+/// the unchanged ones are left out.
+///
+/// Takes time and memory proportional to the product of the amounts of lines, it's meant for
+/// config files.
+pub fn line_diff(before: &str, after: &str) -> Vec<String> {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+
+    // Length of the longest common subsequence of the lines starting at each pair of positions
+    let mut common = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            common[i][j] = if before[i] == after[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            i += 1;
+            j += 1;
+        } else if j == after.len() || (i < before.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("-{}", before[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", after[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_lines_are_shown() {
+        let before = "theme = dark\nfont = mono\nsize = 12\n";
+        let after = "theme = light\nfont = mono\nsize = 12\nzoom = 2\n";
+        assert_eq!(line_diff(before, after), ["-theme = dark", "+theme = light", "+zoom = 2"]);
+        assert!(line_diff(before, before).is_empty());
+        assert_eq!(line_diff("", "a"), ["+a"]);
+        assert_eq!(line_diff("a", ""), ["-a"]);
+    }
+
+    #[test]
+    fn untracked_files_of_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = indoc::indoc!(
+            r#"
+            # tsml-version: 2
+            - [zsh]
+            ".zshrc"
+            ".config": [
+                "nvim"
+                "zsh": ["aliases.zsh"]
+            ]
+            "#
+        );
+        fs::write(dir.path().join(util::TREE_FILE_NAME), tree).unwrap();
+        let files = [
+            ".zshrc",
+            ".zshrc.swp",
+            ".#.zshrc",
+            "notes.txt",
+            ".config/nvim/init.lua",
+            ".config/zsh/aliases.zsh",
+            ".config/zsh/aliases.zsh~",
+            ".config/zsh/plugins/git.zsh",
+        ];
+        for file in files.iter() {
+            let path = dir.path().join("zsh").join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let groups = util::load_groups_from_path(dir.path().join(util::TREE_FILE_NAME)).unwrap();
+        let mut untracked = untracked_files(&groups.map, dir.path()).unwrap();
+        untracked.sort();
+        // Editor files are ignored, and `nvim` covers what's inside of it
+        let expected = [
+            ("zsh", PathBuf::from(".config/zsh/plugins/git.zsh")),
+            ("zsh", PathBuf::from("notes.txt")),
+        ];
+        assert_eq!(untracked, expected);
+    }
+}
@@ -0,0 +1,1551 @@
+//! High level API, used by the CLI and by anyone who wants to drive dotao from Rust.
+//!
+//! An [`Engine`] is loaded from a dotfiles folder, it computes a [`StatusDiff`], turns it into a
+//! [`Plan`] according to a [`ConflictStrategy`], and applies it, returning an [`ApplyReport`].
+//!
+//! Nothing in here prints or exits, errors are returned as [`DotaoError`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fmt, fs,
+    os::unix::fs::symlink,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use file_type_enum::FileType as FileTypeEnum;
+use permissions::PermissionBits;
+
+use crate::{
+    alternates::{self, Facts},
+    config::{Config, ConflictStrategy, LinkStyle, PartialConfig},
+    diff::{self, EntryLink, StatusDiff},
+    error::{DotaoError, DotaoResult},
+    history::{self, Action, Recorder},
+    util::{self, to_utf, TREE_FILE_NAME},
+};
+
+/// Where the links of `file` go, its target path inside of the target root of `group` (the path
+/// of the entry, with the hidden prefix of the group applied and the conditions of alternates
+/// removed), followed by its aliases, also inside of it.
+pub fn link_locations(file: &tsml::FileTree, group: &str, config: &Config) -> Vec<PathBuf> {
+    let target_root = config.target_root_of(group);
+    let tags = file.extra().as_ref();
+    let target_path = tags.and_then(|tags| tags.target_path.as_ref()).unwrap_or(file.path());
+    let target_path = alternates::target_path(target_path);
+    let aliases = tags.map(|tags| tags.aliases.as_slice()).unwrap_or_default();
+    std::iter::once(target_path.as_path())
+        .chain(aliases.iter().map(PathBuf::as_path))
+        .map(|path| target_root.join(path))
+        .collect()
+}
+
+/// What the link of `file` at `location` should point to, depends on the configured link style,
+/// links of system groups are always absolute.
+///
+/// Symlink entries are linked to their own target, as written in the tree file.
+pub fn construct_link_target(
+    file: &tsml::FileTree,
+    group: &str,
+    location: &Path,
+    dotfiles_dir: &Path,
+    config: &Config,
+) -> PathBuf {
+    if let tsml::FileTree::Symlink { target_path, .. } = file {
+        return target_path.clone();
+    }
+    let source_location = dotfiles_dir.join(group).join(file.path());
+    match config.link_style {
+        _ if config.is_system_group(group) => source_location,
+        LinkStyle::Absolute => source_location,
+        // Safe unwrap, `location` has at least the file name in it
+        LinkStyle::Relative => util::relative_path(location.parent().unwrap(), &source_location),
+    }
+}
+
+// Point the symlink at `location` to `target`, replacing it atomically
+fn replace_symlink(target: &Path, location: &Path) -> DotaoResult<()> {
+    let mut temporary_name = location.file_name().unwrap_or_default().to_os_string();
+    temporary_name.push(".dotao-tmp");
+    let temporary = location.with_file_name(temporary_name);
+
+    symlink(target, &temporary).map_err(|err| DotaoError::io("create link at", &temporary, err))?;
+    fs::rename(&temporary, location).map_err(|err| {
+        let _ = fs::remove_file(&temporary);
+        DotaoError::io("replace link at", location, err)
+    })
+}
+
+// Mode of the file at `path` worth keeping in the tree file, git only restores the executable bit,
+// so files that don't have the usual modes need it
+fn mode_to_capture(path: &Path) -> Option<PermissionBits> {
+    let metadata = fs::symlink_metadata(path).ok().filter(fs::Metadata::is_file)?;
+    let mode = PermissionBits::from(metadata.permissions());
+    let usual_modes = [0o644, 0o664, 0o755, 0o775];
+    (!usual_modes.contains(&mode.bits())).then_some(mode)
+}
+
+// Clean up entries collected from `group_dir`: make paths relative to it, sort them, drop the
+// ignored files (see `util::is_ignored`), keep the tags (and mode) of the entries already in
+// `previous`, and capture the mode of new ones
+fn prepare_collected(
+    files: &mut Vec<tsml::FileTree>,
+    group_dir: &Path,
+    previous: &[tsml::FileTree],
+) {
+    files.retain(|file| {
+        !util::is_ignored(file.path().strip_prefix(group_dir).unwrap_or(file.path()))
+    });
+    for file in files.iter_mut() {
+        let captured_mode = mode_to_capture(file.path());
+        if let Ok(relative) = file.path().strip_prefix(group_dir) {
+            *file.path_mut() = relative.to_path_buf();
+        }
+        let old_entry =
+            previous.iter().flat_map(|tree| tree.files()).find(|old| old.path() == file.path());
+        if let Some(old_entry) = old_entry {
+            *file.extra_mut() = old_entry.extra().clone();
+        } else if captured_mode.is_some() {
+            *file.extra_mut() = Some(tsml::Tags { mode: captured_mode, ..Default::default() });
+        }
+        if let Some(children) = file.children_mut() {
+            prepare_collected(children, group_dir, previous);
+        }
+    }
+    files.sort_by(|a, b| a.path().cmp(b.path()));
+}
+
+// What is created first when linking at `location`, it's the location itself, or the first missing
+// directory of its path, because that's what `mkdir -p` creates
+fn first_missing(location: &Path) -> &Path {
+    let mut first_missing = location;
+    while let Some(parent) = first_missing.parent() {
+        if fs::symlink_metadata(parent).is_ok() {
+            break;
+        }
+        first_missing = parent;
+    }
+    first_missing
+}
+
+// If creating (or replacing) something at `location` needs rights we don't have
+fn lacks_rights(location: &Path) -> bool {
+    !permissions::is_creatable(first_missing(location)).unwrap_or(false)
+}
+
+// Why linking at `location` would fail for lack of permission, with the directory that causes it
+fn find_blocker(link: &PlannedLink) -> Option<(PathBuf, Blocker)> {
+    let location = &link.location;
+    let blocker = |kind| Blocker { location: location.clone(), kind };
+
+    // Search permission on each existing ancestor, from the top
+    let mut ancestors: Vec<&Path> = location.ancestors().skip(1).collect();
+    ancestors.reverse();
+    for dir in ancestors {
+        match fs::metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => {},
+            // No directory can be created there
+            Ok(_) => return Some((dir.to_path_buf(), blocker(BlockerKind::NotADirectory))),
+            Err(_) => break,
+        }
+        if !permissions::is_executable(dir).unwrap_or(false) {
+            return Some((dir.to_path_buf(), blocker(BlockerKind::Search)));
+        }
+    }
+
+    // Safe unwrap, `location` has at least the file name in it
+    let parent = location.parent().unwrap();
+    if link.replaces && !permissions::is_removable(location).unwrap_or(false) {
+        return Some((parent.to_path_buf(), blocker(BlockerKind::Remove)));
+    }
+    let first_missing = first_missing(location);
+    if !permissions::is_creatable(first_missing).unwrap_or(false) {
+        // Safe unwrap, it's `location` or one of its ancestors
+        let dir = first_missing.parent().unwrap();
+        return Some((dir.to_path_buf(), blocker(BlockerKind::Create)));
+    }
+    None
+}
+
+// Operations of a privileged link, run through the escalation command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrivilegedStep {
+    Remove,
+    CreateDirectory,
+    Link,
+}
+
+impl PrivilegedStep {
+    // For error messages
+    fn operation(self) -> &'static str {
+        match self {
+            Self::Remove => "remove",
+            Self::CreateDirectory => "create directory",
+            Self::Link => "create link at",
+        }
+    }
+}
+
+// Steps of a privileged link, with their program and arguments
+fn privileged_steps(link: &PlannedLink) -> Vec<(PrivilegedStep, Vec<OsString>)> {
+    let location = link.location.as_os_str();
+    // Safe unwrap, `location` has at least the file name in it
+    let parent = link.location.parent().unwrap();
+
+    let mut steps = vec![];
+    if link.replaces {
+        let rm = vec!["rm".into(), "-f".into(), "--".into(), location.into()];
+        steps.push((PrivilegedStep::Remove, rm));
+    }
+    if fs::symlink_metadata(parent).is_err() {
+        let mkdir = vec!["mkdir".into(), "-p".into(), "--".into(), parent.into()];
+        steps.push((PrivilegedStep::CreateDirectory, mkdir));
+    }
+    let target = link.target.as_os_str();
+    let ln = vec!["ln".into(), "-s".into(), "--".into(), target.into(), location.into()];
+    steps.push((PrivilegedStep::Link, ln));
+    steps
+}
+
+// How removing what's at `location` is recorded, files are backed up first
+fn removal_action(location: &Path, recorder: &mut Recorder) -> DotaoResult<Action> {
+    let metadata = fs::symlink_metadata(location)
+        .map_err(|err| DotaoError::io("remove (to overwrite)", location, err))?;
+    let location = location.to_path_buf();
+    if metadata.is_symlink() {
+        let target =
+            fs::read_link(&location).map_err(|err| DotaoError::io("read link", &location, err))?;
+        Ok(Action::RemoveLink { location, target })
+    } else {
+        Ok(Action::RemoveFile { backup: recorder.backup(&location)?, location })
+    }
+}
+
+// The dotfiles folder that the link at `location`, pointing to `target`, goes through to reach
+// `source` (relative to the dotfiles folder), if it does
+fn dotfiles_dir_of_link(location: &Path, target: &Path, source: &Path) -> Option<PathBuf> {
+    // Safe unwrap, `location` has at least the file name in it
+    let resolved = util::normalize_path(&location.parent().unwrap().join(target));
+    if !resolved.ends_with(source) {
+        return None;
+    }
+    resolved.ancestors().nth(source.components().count()).map(Path::to_path_buf)
+}
+
+// Copy the content of the file at the location of `entry` to its source, keeping the mode of the
+// source, and replace the file by the link
+fn reabsorb_file(entry: &Reabsorbed, recorder: &mut Recorder) -> DotaoResult<()> {
+    let Reabsorbed { source, location, target, .. } = entry;
+    let content = fs::read(location).map_err(|err| DotaoError::io("read", location, err))?;
+
+    let before = Some(recorder.backup(source)?);
+    fs::write(source, content).map_err(|err| DotaoError::io("write", source, err))?;
+    let after = recorder.backup(source)?;
+    recorder.record(Action::WriteFile { path: source.clone(), before, after });
+
+    let removal = removal_action(location, recorder)?;
+    replace_symlink(target, location)?;
+    recorder.record(removal);
+    recorder.record(Action::CreateLink { location: location.clone(), target: target.clone() });
+    Ok(())
+}
+
+// Set the mode of the source, as in the tree file
+fn fix_mode(
+    mode: &PlannedMode,
+    report: &mut ApplyReport,
+    recorder: &mut Recorder,
+) -> DotaoResult<()> {
+    util::set_mode(&mode.source, mode.mode)?;
+    recorder.record(Action::SetMode {
+        path: mode.source.clone(),
+        before: mode.current,
+        after: mode.mode,
+    });
+    report.modes_set.push(mode.source.clone());
+    Ok(())
+}
+
+/// Why an entry can't be linked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// The entry is in the tree file, but not in the group folder.
+    MissingSource,
+    /// There's a symlink at the location, pointing somewhere else (the contained path).
+    LinkedElsewhere(PathBuf),
+    /// There's something else at the location.
+    NotASymlink(FileTypeEnum),
+    /// The location can't be inspected.
+    PermissionDenied,
+    /// dotao linked the location, but it's a regular file now, an application replaced the link
+    /// when saving, see [`Engine::reabsorb`].
+    ReplacedByFile,
+    /// Like `ReplacedByFile`, but the source was modified after the file.
+    ReplacedByOlderFile,
+}
+
+/// An entry that can't be linked as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub group: String,
+    /// Path of the entry, relative to its group.
+    pub file: PathBuf,
+    /// Where the link should be.
+    pub location: PathBuf,
+    pub kind: ConflictKind,
+}
+
+impl Conflict {
+    /// If `ConflictStrategy::Overwrite` is allowed to replace what's at the location (never
+    /// directories).
+    pub fn is_replaceable(&self) -> bool {
+        match &self.kind {
+            ConflictKind::LinkedElsewhere(..) => true,
+            ConflictKind::NotASymlink(file_type) => !file_type.is_directory(),
+            // Has changes that are only in there
+            ConflictKind::MissingSource
+            | ConflictKind::PermissionDenied
+            | ConflictKind::ReplacedByFile
+            | ConflictKind::ReplacedByOlderFile => false,
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ConflictKind::MissingSource => write!(
+                f,
+                "Source file for '{}' is missing from the group '{}'.",
+                to_utf(&self.file),
+                self.group
+            ),
+            ConflictKind::LinkedElsewhere(found) => write!(
+                f,
+                "There is already a link at '{}', but it links to another file:\n    found '{}'.",
+                to_utf(&self.location),
+                to_utf(found)
+            ),
+            ConflictKind::NotASymlink(file_type) => {
+                write!(f, "There is already a {} at '{}'.", file_type, to_utf(&self.location))
+            },
+            ConflictKind::PermissionDenied => {
+                write!(f, "Permission denied to apply links to '{}'.", to_utf(&self.location))
+            },
+            ConflictKind::ReplacedByFile => write!(
+                f,
+                "The link at '{}' was replaced by a regular file, run `dotao reabsorb` to keep its \
+                 changes.",
+                to_utf(&self.location)
+            ),
+            ConflictKind::ReplacedByOlderFile => write!(
+                f,
+                "The link at '{0}' was replaced by a regular file, but its source changed since, run \
+                 `dotao reabsorb '{0}'` to keep the file anyway.",
+                to_utf(&self.location)
+            ),
+        }
+    }
+}
+
+/// What's missing to do an operation of a plan, or what's in the way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockerKind {
+    /// Search (execute) permission, to reach the location through the directory.
+    Search,
+    /// Write permission to the directory, to create the link (or the directories leading to it).
+    Create,
+    /// Write permission to the directory, to remove what's in the place of the link.
+    Remove,
+    /// A directory in the path of the location, which is something else instead.
+    NotADirectory,
+}
+
+/// A planned link that would fail for lack of permission, see [`Engine::preflight`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blocker {
+    /// Where the link should be.
+    pub location: PathBuf,
+    pub kind: BlockerKind,
+}
+
+impl fmt::Display for Blocker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operation = match self.kind {
+            BlockerKind::Search => "reach",
+            BlockerKind::Create => "create",
+            BlockerKind::Remove => "replace",
+            BlockerKind::NotADirectory => {
+                return write!(f, "can't create '{}' inside of a file", to_utf(&self.location));
+            },
+        };
+        write!(f, "can't {} '{}'", operation, to_utf(&self.location))
+    }
+}
+
+/// A link to be created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedLink {
+    pub group: String,
+    /// Where the link is created.
+    pub location: PathBuf,
+    /// What the link points to.
+    pub target: PathBuf,
+    /// If there's a file or symlink at `location` to be removed first.
+    pub replaces: bool,
+    /// If it's in a system group and needs rights we don't have, the operations on `location` are
+    /// run through the escalation command, see [`Engine::privileged_commands`].
+    pub privileged: bool,
+}
+
+/// A source file whose mode differs from the one in the tree file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedMode {
+    pub group: String,
+    /// Path of the source file, inside of the group folder.
+    pub source: PathBuf,
+    /// Mode from the tree file.
+    pub mode: PermissionBits,
+    /// Mode the file has now.
+    pub current: PermissionBits,
+}
+
+/// What applying would do, see [`Engine::plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub strategy: ConflictStrategy,
+    /// Locations that are already linked correctly.
+    pub already_linked: Vec<PathBuf>,
+    pub links: Vec<PlannedLink>,
+    /// Sources to have their mode fixed.
+    pub modes: Vec<PlannedMode>,
+    /// Conflicts not solved by the strategy, they are skipped with `ConflictStrategy::Skip`,
+    /// and prevent applying otherwise.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Plan {
+    /// If applying this plan would fail because of conflicts.
+    pub fn is_blocked(&self) -> bool {
+        self.strategy != ConflictStrategy::Skip && !self.conflicts.is_empty()
+    }
+
+    /// If there's nothing to do.
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty() && self.modes.is_empty()
+    }
+}
+
+/// What was done by [`Engine::apply`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    /// Locations of the created links.
+    pub linked: Vec<PathBuf>,
+    /// Locations that had a file or symlink removed before linking.
+    pub replaced: Vec<PathBuf>,
+    /// Conflicts left untouched.
+    pub skipped: Vec<Conflict>,
+    /// Sources that had their mode fixed.
+    pub modes_set: Vec<PathBuf>,
+    /// Hook commands that ran successfully, in order.
+    pub hooks_run: Vec<String>,
+}
+
+/// The entry that provides a target path, see [`Engine::which`].
+#[derive(Debug, Clone)]
+pub struct Owner<'a> {
+    pub group: &'a str,
+    pub file: &'a tsml::FileTree,
+    /// Path of the source file, inside of the group folder.
+    pub source: PathBuf,
+    /// Where the link should be.
+    pub location: PathBuf,
+    /// Line of the tree file where the entry is declared.
+    pub line: Option<usize>,
+}
+
+/// A link restored by [`Engine::reabsorb`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reabsorbed {
+    pub group: String,
+    /// Source file that got the content of the file at the location.
+    pub source: PathBuf,
+    pub location: PathBuf,
+    /// What the restored link points to.
+    pub target: PathBuf,
+    /// The source was modified after the file, it's only reabsorbed when asked for by location.
+    pub source_is_newer: bool,
+}
+
+/// Why a link is rewritten by [`Engine::relink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelinkReason {
+    /// It points into a previous location of the dotfiles folder (the contained path).
+    Moved(PathBuf),
+    /// It points to the right source, but its text doesn't follow the link style.
+    Style,
+}
+
+/// A link that leads to its source through the wrong path, see [`Engine::relinks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relink {
+    pub group: String,
+    pub location: PathBuf,
+    /// What it points to now.
+    pub found: PathBuf,
+    /// What it should point to.
+    pub target: PathBuf,
+    pub reason: RelinkReason,
+}
+
+/// A loaded dotfiles folder, with its tree file and configuration.
+#[derive(Debug, Clone)]
+pub struct Engine {
+    dotfiles_dir: PathBuf,
+    config: Config,
+    groups: tsml::Groups,
+    // Conditions of alternates are checked against them
+    facts: Facts,
+    // Recorded in the history along with each operation
+    command: String,
+}
+
+impl Engine {
+    /// Load the tree file at `dotfiles_dir`, using `config` as it is.
+    pub fn new(dotfiles_dir: impl Into<PathBuf>, config: Config) -> DotaoResult<Self> {
+        let dotfiles_dir = dotfiles_dir.into();
+        let groups = util::load_groups_from_path(dotfiles_dir.join(TREE_FILE_NAME))?;
+        let facts = Facts::detect();
+        Ok(Self { dotfiles_dir, config, groups, facts, command: "dotao".to_string() })
+    }
+
+    /// Check the conditions of alternates against `facts`, instead of the ones of this machine.
+    pub fn with_facts(mut self, facts: Facts) -> Self {
+        self.facts = facts;
+        self
+    }
+
+    /// Set the command shown in the history for the operations of this engine.
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.command = command.into();
+        self
+    }
+
+    /// Load the tree file at `dotfiles_dir`, with the configuration from config files and env
+    /// vars, overridden by `overrides`.
+    pub fn load(dotfiles_dir: impl Into<PathBuf>, overrides: PartialConfig) -> DotaoResult<Self> {
+        let dotfiles_dir = dotfiles_dir.into();
+        let config = Config::load(&dotfiles_dir, overrides)?;
+        Self::new(dotfiles_dir, config)
+    }
+
+    pub fn dotfiles_dir(&self) -> &Path {
+        &self.dotfiles_dir
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn groups(&self) -> &tsml::Groups {
+        &self.groups
+    }
+
+    pub fn facts(&self) -> &Facts {
+        &self.facts
+    }
+
+    /// Alternates of the entries with active tags, with the variant chosen for each.
+    pub fn alternates(&self) -> Vec<alternates::Alternate<'_>> {
+        alternates::alternates(&self.groups.map, &self.config.default_tags, &self.facts)
+    }
+
+    /// State of every entry with active tags.
+    pub fn status(&self) -> DotaoResult<StatusDiff<'_>> {
+        let recorded_links = history::recorded_links(&history::load_history(&self.dotfiles_dir)?);
+        StatusDiff::from_groups_map(
+            &self.groups.map,
+            &self.dotfiles_dir,
+            &self.config,
+            &self.facts,
+            &recorded_links,
+        )
+    }
+
+    /// Files inside of group folders that are missing from the tree file, see
+    /// [`diff::untracked_files`].
+    pub fn untracked(&self) -> DotaoResult<Vec<(&str, PathBuf)>> {
+        diff::untracked_files(&self.groups.map, &self.dotfiles_dir)
+    }
+
+    /// Update the entries of each group in `names` (adding the groups that are new) from their
+    /// folders, and write the tree file. Tags of entries that are still there are kept, and ignored
+    /// files (see [`util::is_ignored`]) are left out.
+    pub fn add_groups(&mut self, names: &[&str]) -> DotaoResult<()> {
+        let tree_file_path = self.dotfiles_dir.join(TREE_FILE_NAME);
+
+        for name in names.iter().map(|name| name.trim_end_matches('/')) {
+            let group_dir = self.dotfiles_dir.join(name);
+            // Symlinks are kept as symlink entries
+            let mut files = tsml::FileTree::collect_from_directory_symlink(&group_dir)
+                .map_err(|source| DotaoError::FileTree { operation: "read group", source })?;
+            let previous = self.groups.map.get(name).map(Vec::as_slice).unwrap_or_default();
+            prepare_collected(&mut files, &group_dir, previous);
+            self.groups.map.insert(name.to_string(), files);
+        }
+
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        self.write_tree_file(&self.groups, &mut recorder)?;
+        recorder.finish()?;
+        // Parse it again, so group tags apply to new entries
+        self.groups = util::load_groups_from_path(&tree_file_path)?;
+        Ok(())
+    }
+
+    /// Set the hidden prefix of `group` (see [`tsml::apply_hidden_prefix`]), written to the tree
+    /// file along with the group by [`Engine::add_groups`].
+    pub fn set_hidden_prefix(&mut self, group: &str, prefix: &str) {
+        self.groups.info.hidden_prefixes.insert(group.to_string(), prefix.to_string());
+    }
+
+    /// Find the entry that provides `path`, a target path (the link doesn't need to exist) or a
+    /// source path inside of the dotfiles folder, entries with inactive tags are included.
+    pub fn which(&self, path: &Path) -> DotaoResult<Option<Owner<'_>>> {
+        let path = util::normalize_path(&util::current_dir()?.join(path));
+        let relative_to_dotfiles = path.strip_prefix(&self.dotfiles_dir).ok();
+
+        // Through any of its links, aliases included, variants of alternates share them, so it's
+        // the chosen one
+        let alternates = self.alternates();
+        let is_owner = |group: &str, file: &tsml::FileTree| {
+            (link_locations(file, group, &self.config).contains(&path)
+                && !alternates.iter().any(|alternate| alternate.leaves_out(file)))
+                || relative_to_dotfiles.and_then(|path| path.strip_prefix(group).ok())
+                    == Some(file.path().as_path())
+        };
+
+        let found = self.groups.map.iter().find_map(|(group, trees)| {
+            trees
+                .iter()
+                .flat_map(|tree| tree.files().skip_dirs(true))
+                .find(|file| is_owner(group, file))
+                .map(|file| (group, file))
+        });
+        let (group, file) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let tree_file_path = self.dotfiles_dir.join(TREE_FILE_NAME);
+        let text = fs::read_to_string(&tree_file_path)
+            .map_err(|err| DotaoError::io("read", &tree_file_path, err))?;
+        let line =
+            tsml::declaration_lines(&text).get(&(group.clone(), file.path().clone())).copied();
+
+        // The alias that was asked for, if it was one
+        let locations = link_locations(file, group, &self.config);
+        let location =
+            locations.iter().find(|location| **location == path).unwrap_or(&locations[0]);
+        Ok(Some(Owner {
+            group,
+            file,
+            source: self.dotfiles_dir.join(group).join(file.path()),
+            location: location.clone(),
+            line,
+        }))
+    }
+
+    /// Compute what `apply` would do with `strategy`, without touching anything.
+    pub fn plan(&self, strategy: ConflictStrategy) -> DotaoResult<Plan> {
+        let diff = self.status()?;
+        let needs_privileges = |location: &Path, group: &str| {
+            self.config.is_system_group(group) && lacks_rights(location)
+        };
+
+        let planned_link = |link: &EntryLink, replaces| PlannedLink {
+            group: link.group.to_string(),
+            location: link.location.clone(),
+            target: construct_link_target(
+                link.file,
+                link.group,
+                &link.location,
+                &self.dotfiles_dir,
+                &self.config,
+            ),
+            replaces,
+            privileged: needs_privileges(&link.location, link.group),
+        };
+        let conflict = |link: &EntryLink, kind| Conflict {
+            group: link.group.to_string(),
+            file: link.file.path().to_path_buf(),
+            location: link.location.clone(),
+            kind,
+        };
+
+        let already_linked =
+            diff.linked_correctly.iter().map(|link| link.location.clone()).collect();
+        let mut links: Vec<PlannedLink> =
+            diff.ready_to_link.iter().map(|link| planned_link(link, false)).collect();
+        // Replacing a link of dotao, regardless of the strategy
+        links.extend(diff.switched_variant.iter().map(|(link, _)| planned_link(link, true)));
+
+        let modes = diff
+            .wrong_mode
+            .iter()
+            .filter_map(|(file, group, current)| {
+                Some(PlannedMode {
+                    group: group.to_string(),
+                    source: self.dotfiles_dir.join(group).join(file.path()),
+                    mode: file.extra().as_ref()?.mode?,
+                    current: *current,
+                })
+            })
+            .collect();
+
+        // Along with the link, to plan it when overwriting
+        let mut conflicts = vec![];
+        for (file, group) in diff.missing_source.iter() {
+            // Once per entry, at the target path, not at each alias
+            let location = link_locations(file, group, &self.config).remove(0);
+            let link = EntryLink { file, group, location };
+            conflicts.push((conflict(&link, ConflictKind::MissingSource), link));
+        }
+        for (link, found) in diff.linked_incorrectly.iter() {
+            let kind = ConflictKind::LinkedElsewhere(found.clone());
+            conflicts.push((conflict(link, kind), link.clone()));
+        }
+        for link in diff.no_permission_to_link_to_target.iter() {
+            if self.config.is_system_group(link.group) {
+                // Can't be inspected, `ln` fails without touching anything if it's taken
+                links.push(PlannedLink { privileged: true, ..planned_link(link, false) });
+            } else {
+                conflicts.push((conflict(link, ConflictKind::PermissionDenied), link.clone()));
+            }
+        }
+        for (link, file_type) in diff.not_a_symlink.iter() {
+            let kind = ConflictKind::NotASymlink(*file_type);
+            conflicts.push((conflict(link, kind), link.clone()));
+        }
+        for link in diff.replaced_by_file.iter() {
+            conflicts.push((conflict(link, ConflictKind::ReplacedByFile), link.clone()));
+        }
+        for link in diff.replaced_by_older_file.iter() {
+            conflicts.push((conflict(link, ConflictKind::ReplacedByOlderFile), link.clone()));
+        }
+
+        if strategy == ConflictStrategy::Overwrite {
+            let (replaceable, unsolved): (Vec<_>, Vec<_>) =
+                conflicts.into_iter().partition(|(conflict, _)| conflict.is_replaceable());
+            links.extend(replaceable.iter().map(|(_, link)| planned_link(link, true)));
+            conflicts = unsolved;
+        }
+        let conflicts = conflicts.into_iter().map(|(conflict, _)| conflict).collect();
+
+        Ok(Plan { strategy, already_linked, links, modes, conflicts })
+    }
+
+    /// Check every link of `plan` against the permissions of its directories, and for files in
+    /// place of them, without touching anything. Returns the links that would fail, by the
+    /// directory (or file) that causes it.
+    ///
+    /// Privileged links are left out, they go through the escalation command.
+    pub fn preflight(&self, plan: &Plan) -> BTreeMap<PathBuf, Vec<Blocker>> {
+        let mut blockers = BTreeMap::<PathBuf, Vec<Blocker>>::new();
+        let links = plan.links.iter().filter(|link| !link.privileged);
+        for (dir, blocker) in links.filter_map(find_blocker) {
+            blockers.entry(dir).or_default().push(blocker);
+        }
+        blockers
+    }
+
+    /// Run hooks, create the links of `plan` and fix the modes of sources, fails if the plan is
+    /// blocked by conflicts, or if any link would fail for lack of permission (see
+    /// [`Engine::preflight`]).
+    pub fn apply(&self, plan: &Plan) -> DotaoResult<ApplyReport> {
+        if plan.is_blocked() {
+            return Err(DotaoError::Conflicts { amount: plan.conflicts.len() });
+        }
+
+        let mut report = ApplyReport { skipped: plan.conflicts.clone(), ..Default::default() };
+        if plan.is_empty() {
+            return Ok(report);
+        }
+        let blocked: usize = self.preflight(plan).values().map(Vec::len).sum();
+        if blocked > 0 {
+            return Err(DotaoError::Blocked { amount: blocked });
+        }
+
+        self.run_hooks("pre_link", &self.config.hooks.pre_link, &mut report)?;
+
+        // Record what was done even if it fails midway, so it can be undone
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        let result = plan
+            .links
+            .iter()
+            .try_for_each(|link| self.create_link(link, &mut report, &mut recorder))
+            .and_then(|()| {
+                plan.modes.iter().try_for_each(|mode| fix_mode(mode, &mut report, &mut recorder))
+            });
+        recorder.finish()?;
+        result?;
+
+        self.run_hooks("post_link", &self.config.hooks.post_link, &mut report)?;
+
+        Ok(report)
+    }
+
+    /// Commands that `apply` runs through the escalation command for the privileged links of
+    /// `plan`, in order, as they should be shown before applying.
+    pub fn privileged_commands(&self, plan: &Plan) -> Vec<Vec<OsString>> {
+        let links = plan.links.iter().filter(|link| link.privileged);
+        let steps = links.flat_map(privileged_steps);
+        steps.map(|(_, command)| self.escalated(command)).collect()
+    }
+
+    // `command` prefixed by the escalation command
+    fn escalated(&self, command: Vec<OsString>) -> Vec<OsString> {
+        let escalation = self.config.escalation_command.split_whitespace().map(OsString::from);
+        escalation.chain(command).collect()
+    }
+
+    fn run_privileged(
+        &self,
+        operation: &str,
+        location: &Path,
+        command: Vec<OsString>,
+    ) -> DotaoResult<()> {
+        let command = self.escalated(command);
+        let command_error = |reason: String| DotaoError::Command {
+            command: command.iter().map(to_utf).collect::<Vec<_>>().join(" "),
+            reason: format!("failed to {} '{}', {}", operation, to_utf(location), reason),
+        };
+        // Safe index, the escalation command can't be empty
+        let status = Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+            .map_err(|err| command_error(format!("could not run: {}", err)))?;
+        if !status.success() {
+            return Err(command_error(format!("exited with {}", status)));
+        }
+        Ok(())
+    }
+
+    // Create the link, removing what's in its place first if it `replaces` (files are backed up),
+    // privileged links go through the escalation command
+    fn create_link(
+        &self,
+        link: &PlannedLink,
+        report: &mut ApplyReport,
+        recorder: &mut Recorder,
+    ) -> DotaoResult<()> {
+        let location = &link.location;
+        if link.privileged {
+            for (step, command) in privileged_steps(link) {
+                // Reading doesn't need privileges, so the backup is done before removing
+                let removed = if step == PrivilegedStep::Remove {
+                    Some(removal_action(location, recorder)?)
+                } else {
+                    None
+                };
+                self.run_privileged(step.operation(), location, command)?;
+                if let Some(action) = removed {
+                    recorder.record(action);
+                    report.replaced.push(location.clone());
+                }
+            }
+        } else {
+            if link.replaces {
+                let action = removal_action(location, recorder)?;
+                fs::remove_file(location)
+                    .map_err(|err| DotaoError::io("remove (to overwrite)", location, err))?;
+                recorder.record(action);
+                report.replaced.push(location.clone());
+            }
+            // Safe unwrap, `location` has at least the file name in it
+            let parent = location.parent().unwrap();
+            fs::create_dir_all(parent)
+                .map_err(|err| DotaoError::io("create directory", parent, err))?;
+            symlink(&link.target, location)
+                .map_err(|err| DotaoError::io("create link at", location, err))?;
+        }
+        recorder
+            .record(Action::CreateLink { location: location.clone(), target: link.target.clone() });
+        report.linked.push(location.clone());
+        Ok(())
+    }
+
+    // Overwrite the tree file, keeping copies of its content before and after
+    fn write_tree_file(&self, groups: &tsml::Groups, recorder: &mut Recorder) -> DotaoResult<()> {
+        let path = self.dotfiles_dir.join(TREE_FILE_NAME);
+        let before = if path.exists() { Some(recorder.backup(&path)?) } else { None };
+        util::write_groups_to_path(groups, &path)?;
+        let after = match recorder.backup(&path) {
+            Ok(after) => after,
+            Err(cause) => {
+                // Put the previous one back, it's not recorded
+                let restored = match &before {
+                    Some(before) => fs::rename(before, &path),
+                    None => fs::remove_file(&path),
+                };
+                return Err(match restored {
+                    Ok(()) => cause,
+                    Err(err) => DotaoError::RollbackFailed {
+                        cause: Box::new(cause),
+                        rollback_error: Box::new(DotaoError::io("restore", &path, err)),
+                    },
+                });
+            },
+        };
+        recorder.record(Action::WriteFile { path, before, after });
+        Ok(())
+    }
+
+    /// Links that were replaced by regular files (see [`ConflictKind::ReplacedByFile`]), as
+    /// [`Engine::reabsorb`] would restore them.
+    pub fn replaced_links(&self) -> DotaoResult<Vec<Reabsorbed>> {
+        let diff = self.status()?;
+        let older = diff.replaced_by_older_file.iter().map(|link| (link, true));
+        let replaced = diff.replaced_by_file.iter().map(|link| (link, false)).chain(older);
+        let replaced = replaced.map(|(link, source_is_newer)| Reabsorbed {
+            group: link.group.to_string(),
+            source: self.dotfiles_dir.join(link.group).join(link.file.path()),
+            location: link.location.clone(),
+            target: construct_link_target(
+                link.file,
+                link.group,
+                &link.location,
+                &self.dotfiles_dir,
+                &self.config,
+            ),
+            source_is_newer,
+        });
+        Ok(replaced.collect())
+    }
+
+    /// Move the content of the regular files that replaced links back into their sources, and
+    /// restore the links, only the ones at `locations` if any is given. The files are backed up, so
+    /// it can be undone.
+    ///
+    /// Files older than their source would drop its changes, they're skipped unless their location
+    /// is given. Returns the reabsorbed links and the skipped ones.
+    pub fn reabsorb(
+        &self,
+        locations: &[PathBuf],
+    ) -> DotaoResult<(Vec<Reabsorbed>, Vec<Reabsorbed>)> {
+        let current_dir = util::current_dir()?;
+        let locations: Vec<PathBuf> = locations
+            .iter()
+            .map(|location| util::normalize_path(&current_dir.join(location)))
+            .collect();
+
+        let replaced = self.replaced_links()?;
+        let unknown = locations
+            .iter()
+            .find(|location| !replaced.iter().any(|entry| entry.location == **location));
+        if let Some(location) = unknown {
+            return Err(DotaoError::invalid_usage(
+                format!("'{}' is not a link that was replaced by a file", to_utf(location)),
+                "run `dotao status` to see the replaced links",
+            ));
+        }
+        let (chosen, skipped): (Vec<Reabsorbed>, Vec<Reabsorbed>) = replaced
+            .into_iter()
+            .filter(|entry| locations.is_empty() || locations.contains(&entry.location))
+            .partition(|entry| !entry.source_is_newer || !locations.is_empty());
+
+        // Record what was done even if it fails midway, so it can be undone
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        let result = chosen.iter().try_for_each(|entry| reabsorb_file(entry, &mut recorder));
+        recorder.finish()?;
+        result.map(|()| (chosen, skipped))
+    }
+
+    /// Where the dotfiles folder was when its links were created, according to the history,
+    /// besides its current location.
+    pub fn previous_locations(&self) -> DotaoResult<BTreeSet<PathBuf>> {
+        // Path of each entry inside of the dotfiles folder, by the location of its link
+        let sources: BTreeMap<PathBuf, PathBuf> = self
+            .groups
+            .map
+            .iter()
+            .flat_map(|(group, trees)| {
+                let files = trees.iter().flat_map(|tree| tree.files().skip_dirs(true));
+                files.flat_map(move |file| {
+                    let source = Path::new(group).join(file.path());
+                    let locations = link_locations(file, group, &self.config);
+                    locations.into_iter().map(move |location| (location, source.clone()))
+                })
+            })
+            .collect();
+
+        let history = history::load_history(&self.dotfiles_dir)?;
+        let created =
+            history.iter().flat_map(|operation| operation.actions.iter()).filter_map(|action| {
+                match action {
+                    Action::CreateLink { location, target } => Some((location, target)),
+                    _ => None,
+                }
+            });
+        let locations = created
+            .filter_map(|(location, target)| {
+                dotfiles_dir_of_link(location, target, sources.get(location)?)
+            })
+            .filter(|dir| *dir != self.dotfiles_dir)
+            .collect();
+        Ok(locations)
+    }
+
+    /// Links of entries that reach their source through a previous location of the dotfiles
+    /// folder (from [`Engine::previous_locations`] or `previous`), or through a path that doesn't
+    /// follow the link style, with what they should point to.
+    pub fn relinks(&self, previous: &[PathBuf]) -> DotaoResult<Vec<Relink>> {
+        let current_dir = util::current_dir()?;
+        let mut previous_locations = self.previous_locations()?;
+        previous_locations
+            .extend(previous.iter().map(|dir| util::normalize_path(&current_dir.join(dir))));
+
+        let diff = self.status()?;
+        let mut relinks = vec![];
+        // Symlink entries don't point into the dotfiles folder
+        for (link, found) in
+            diff.linked_incorrectly.iter().filter(|(link, _)| !link.file.is_symlink())
+        {
+            let EntryLink { file, group, location } = link;
+            let source = Path::new(group).join(file.path());
+            let reason = match dotfiles_dir_of_link(location, found, &source) {
+                Some(dir) if dir == self.dotfiles_dir => RelinkReason::Style,
+                Some(dir) if previous_locations.contains(&dir) => RelinkReason::Moved(dir),
+                _ => continue,
+            };
+            relinks.push(Relink {
+                group: group.to_string(),
+                location: location.clone(),
+                found: found.clone(),
+                target: construct_link_target(
+                    file,
+                    group,
+                    location,
+                    &self.dotfiles_dir,
+                    &self.config,
+                ),
+                reason,
+            });
+        }
+        Ok(relinks)
+    }
+
+    /// Point each link of `relinks` to its target, replacing them in place.
+    pub fn relink(&self, relinks: &[Relink]) -> DotaoResult<()> {
+        // Record what was done even if it fails midway, so it can be undone
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        let result = relinks.iter().try_for_each(|relink| {
+            replace_symlink(&relink.target, &relink.location)?;
+            recorder.record(Action::RemoveLink {
+                location: relink.location.clone(),
+                target: relink.found.clone(),
+            });
+            recorder.record(Action::CreateLink {
+                location: relink.location.clone(),
+                target: relink.target.clone(),
+            });
+            Ok(())
+        });
+        recorder.finish()?;
+        result
+    }
+
+    /// Remove the links of `groups` (every group if empty) that point to their sources, aliases
+    /// included, the rest is left as it is. Returns the removed links.
+    pub fn unlink(&self, groups: &[&str]) -> DotaoResult<Vec<PathBuf>> {
+        let unknown = groups.iter().find(|group| !self.groups.map.contains_key(**group));
+        if let Some(group) = unknown {
+            return Err(DotaoError::invalid_usage(
+                format!("there's no group '{}' in the tree file", group),
+                "run `dotao list` to see every group",
+            ));
+        }
+
+        let diff = self.status()?;
+        let links = diff.linked_correctly.iter().filter(|link| {
+            let is_chosen = groups.is_empty() || groups.contains(&link.group);
+            // Files reached through a linked parent directory aren't links themselves
+            let is_symlink = fs::symlink_metadata(&link.location)
+                .is_ok_and(|metadata| metadata.file_type().is_symlink());
+            is_chosen && is_symlink
+        });
+
+        // Record what was done even if it fails midway, so it can be undone
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        let mut removed = vec![];
+        let result = links.into_iter().try_for_each(|link| {
+            let target = fs::read_link(&link.location)
+                .map_err(|err| DotaoError::io("read symlink", &link.location, err))?;
+            fs::remove_file(&link.location)
+                .map_err(|err| DotaoError::io("remove link at", &link.location, err))?;
+            recorder.record(Action::RemoveLink { location: link.location.clone(), target });
+            removed.push(link.location.clone());
+            Ok(())
+        });
+        recorder.finish()?;
+        result.map(|()| removed)
+    }
+
+    /// Rewrite the tree file to the current version of the syntax, keeping its comments.
+    ///
+    /// Returns the version it was at, `None` if it was already current.
+    pub fn migrate_tree_file(&mut self) -> DotaoResult<Option<u32>> {
+        let path = self.dotfiles_dir.join(TREE_FILE_NAME);
+        let version = self.groups.info.version;
+        if version == tsml::CURRENT_VERSION {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(&path).map_err(|err| DotaoError::io("read", &path, err))?;
+        let migrated = tsml::migrate(&text).map_err(|source| DotaoError::tsml(&path, source))?;
+
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        let before = Some(recorder.backup(&path)?);
+        fs::write(&path, migrated).map_err(|err| DotaoError::io("write", &path, err))?;
+        let after = recorder.backup(&path)?;
+        recorder.record(Action::WriteFile { path: path.clone(), before, after });
+        recorder.finish()?;
+
+        self.groups = util::load_groups_from_path(&path)?;
+        Ok(Some(version))
+    }
+
+    /// Rename group `old` to `new`: its folder, its header in the tree file (keeping position and
+    /// tags) and the links pointing into it. If any step fails, the previous ones are rolled back.
+    ///
+    /// Returns the locations of the rewritten links.
+    pub fn move_group(&mut self, old: &str, new: &str) -> DotaoResult<Vec<PathBuf>> {
+        let (old, new) = (old.trim_end_matches('/'), new.trim_end_matches('/'));
+        let old_dir = self.dotfiles_dir.join(old);
+        let new_dir = self.dotfiles_dir.join(new);
+
+        // Checks
+        let files = self.groups.map.get(old).ok_or_else(|| {
+            DotaoError::invalid_usage(
+                format!("there's no group '{}' in the tree file", old),
+                "run `dotao list` to see every group",
+            )
+        })?;
+        let is_valid_name = !new.is_empty()
+            && new != "main"
+            && Path::new(new)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_valid_name {
+            return Err(DotaoError::invalid_usage(
+                format!("'{}' is not a valid group name", new),
+                "use a path relative to the dotfiles folder, without '.' or '..'",
+            ));
+        }
+        if self.groups.map.contains_key(new) || fs::symlink_metadata(&new_dir).is_ok() {
+            return Err(DotaoError::invalid_usage(
+                format!("group '{}' already exists", new),
+                "choose another name, or remove the existing group first",
+            ));
+        }
+        // Links of system groups need privileges, and the config would need to change too
+        if self.config.is_system_group(old) || self.config.is_system_group(new) {
+            return Err(DotaoError::invalid_usage(
+                format!("can't rename '{}' to '{}', system groups can't be renamed", old, new),
+                "rename the folder and update `system_groups` in the config by hand",
+            ));
+        }
+
+        // Links currently pointing into the old folder, with their old and new targets
+        let links: Vec<(PathBuf, PathBuf, PathBuf)> = files
+            .iter()
+            .flat_map(|tree| tree.files().skip_dirs(true))
+            // Symlink entries don't point into the group folder
+            .filter(|file| !file.is_symlink())
+            // Aliases keep their location, but move along with the source
+            .flat_map(|file| {
+                link_locations(file, old, &self.config).into_iter().map(move |location| (file, location))
+            })
+            .filter_map(|(file, location)| {
+                let target_of = |group| {
+                    construct_link_target(file, group, &location, &self.dotfiles_dir, &self.config)
+                };
+                let (old_target, new_target) = (target_of(old), target_of(new));
+                let is_linked = fs::read_link(&location).ok()? == old_target;
+                is_linked.then_some((location, old_target, new_target))
+            })
+            .collect();
+
+        let mut recorder = Recorder::start(&self.dotfiles_dir, &self.command)?;
+        fs::rename(&old_dir, &new_dir)
+            .map_err(|err| DotaoError::io("rename group folder", &old_dir, err))?;
+        recorder.record(Action::RenameDir { from: old_dir.clone(), to: new_dir.clone() });
+
+        let mut relinked = vec![];
+        let result = links
+            .iter()
+            .try_for_each(|(location, old_target, new_target)| {
+                replace_symlink(new_target, location)?;
+                recorder.record(Action::RemoveLink {
+                    location: location.clone(),
+                    target: old_target.clone(),
+                });
+                recorder.record(Action::CreateLink {
+                    location: location.clone(),
+                    target: new_target.clone(),
+                });
+                relinked.push(location.clone());
+                Ok(())
+            })
+            .and_then(|()| {
+                let mut groups = self.groups.clone();
+                groups.rename_group(old, new);
+                self.write_tree_file(&groups, &mut recorder)?;
+                Ok(groups)
+            });
+
+        let cause = match result {
+            Ok(groups) => {
+                self.groups = groups;
+                recorder.finish()?;
+                return Ok(relinked);
+            },
+            Err(err) => err,
+        };
+        recorder.discard();
+
+        // Roll back in reverse order, keeping the first error
+        let mut rollback_error = None;
+        for (location, old_target, _) in links.iter().rev() {
+            if relinked.contains(location) {
+                if let Err(err) = replace_symlink(old_target, location) {
+                    rollback_error.get_or_insert(err);
+                }
+            }
+        }
+        if let Err(err) = fs::rename(&new_dir, &old_dir) {
+            rollback_error.get_or_insert(DotaoError::io("rename group folder", &new_dir, err));
+        }
+
+        Err(match rollback_error {
+            Some(rollback_error) => DotaoError::RollbackFailed {
+                cause: Box::new(cause),
+                rollback_error: Box::new(rollback_error),
+            },
+            None => cause,
+        })
+    }
+
+    // Run each hook command with `sh`, inside of the dotfiles folder
+    fn run_hooks(
+        &self,
+        name: &str,
+        commands: &[String],
+        report: &mut ApplyReport,
+    ) -> DotaoResult<()> {
+        for command in commands {
+            let hook_error = |reason: String| DotaoError::Command {
+                command: command.clone(),
+                reason: format!("{} hook {}", name, reason),
+            };
+            let status = Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .current_dir(&self.dotfiles_dir)
+                .status()
+                .map_err(|err| hook_error(format!("could not run: {}", err)))?;
+            if !status.success() {
+                return Err(hook_error(format!("exited with {}", status)));
+            }
+            report.hooks_run.push(command.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::fs::PermissionsExt,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+
+    // Engine of a dotfiles folder inside of `dir`, with `tree` as the tree file, linking to `dir`
+    fn engine_at(dir: &Path, tree: &str) -> Engine {
+        engine_with(dir, tree, PartialConfig::default())
+    }
+
+    fn engine_with(dir: &Path, tree: &str, config: PartialConfig) -> Engine {
+        let dotfiles_dir = dir.join("dotfiles");
+        fs::create_dir_all(&dotfiles_dir).unwrap();
+        fs::write(dotfiles_dir.join(TREE_FILE_NAME), format!("# tsml-version: 2\n{}", tree))
+            .unwrap();
+        let config = config.finish(&dotfiles_dir);
+        Engine::new(dotfiles_dir, config).unwrap()
+    }
+
+    fn is_root() -> bool {
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    fn planned(location: PathBuf, replaces: bool) -> PlannedLink {
+        let target = PathBuf::from("dotfiles/app/file");
+        PlannedLink { group: "app".to_string(), location, target, replaces, privileged: false }
+    }
+
+    fn plan_of(links: Vec<PlannedLink>) -> Plan {
+        let strategy = ConflictStrategy::Overwrite;
+        Plan { strategy, already_linked: vec![], links, modes: vec![], conflicts: vec![] }
+    }
+
+    #[test]
+    fn file_blocking_a_parent_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_at(dir.path(), "");
+        fs::write(dir.path().join(".config"), "").unwrap();
+
+        let location = dir.path().join(".config/app/file");
+        let blockers = engine.preflight(&plan_of(vec![planned(location.clone(), false)]));
+        let blocker = Blocker { location, kind: BlockerKind::NotADirectory };
+        assert_eq!(blockers.len(), 1);
+        assert_eq!(blockers[&dir.path().join(".config")], [blocker]);
+    }
+
+    #[test]
+    fn file_blocking_a_leaf() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_at(dir.path(), "");
+        let location = dir.path().join(".zshrc");
+        fs::write(&location, "").unwrap();
+
+        // Replaced when overwriting
+        let plan = plan_of(vec![planned(location.clone(), true)]);
+        assert!(engine.preflight(&plan).is_empty());
+
+        // Unless its directory can't be written to, which root ignores
+        let read_only = dir.path().join("read-only");
+        fs::create_dir(&read_only).unwrap();
+        let location = read_only.join(".zshrc");
+        fs::write(&location, "").unwrap();
+        fs::set_permissions(&read_only, fs::Permissions::from_mode(0o555)).unwrap();
+        let blockers = engine.preflight(&plan_of(vec![planned(location.clone(), true)]));
+        fs::set_permissions(&read_only, fs::Permissions::from_mode(0o755)).unwrap();
+
+        if !is_root() {
+            let blocker = Blocker { location, kind: BlockerKind::Remove };
+            assert_eq!(blockers.len(), 1);
+            assert_eq!(blockers[&read_only], [blocker]);
+        }
+    }
+
+    #[test]
+    fn steps_of_privileged_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("missing/hosts");
+        let link = PlannedLink { privileged: true, ..planned(location.clone(), false) };
+        let steps = privileged_steps(&link);
+        let kinds: Vec<PrivilegedStep> = steps.iter().map(|(step, _)| *step).collect();
+        assert_eq!(kinds, [PrivilegedStep::CreateDirectory, PrivilegedStep::Link]);
+        let mkdir = ["mkdir", "-p", "--"].iter().map(OsString::from);
+        let parent = dir.path().join("missing").into_os_string();
+        assert_eq!(steps[0].1, mkdir.chain(Some(parent)).collect::<Vec<_>>());
+        let ln = ["ln", "-s", "--", "dotfiles/app/file"].iter().map(OsString::from);
+        assert_eq!(steps[1].1, ln.chain(Some(location.into_os_string())).collect::<Vec<_>>());
+
+        let link = PlannedLink { privileged: true, ..planned(dir.path().join("hosts"), true) };
+        let kinds: Vec<PrivilegedStep> =
+            privileged_steps(&link).iter().map(|(step, _)| *step).collect();
+        assert_eq!(kinds, [PrivilegedStep::Remove, PrivilegedStep::Link]);
+    }
+
+    #[test]
+    fn only_system_groups_escalate() {
+        let dir = tempfile::tempdir().unwrap();
+        let target_root = dir.path().join("home");
+        fs::create_dir(&target_root).unwrap();
+        let config = PartialConfig {
+            target_root: Some(target_root.clone()),
+            system_groups: Some(vec!["etc".to_string()]),
+            escalation_command: Some("doas -n".to_string()),
+            ..PartialConfig::default()
+        };
+        let tree = "- [etc]\n\"etc\": [\"dotao-test-hosts\"]\n- [zsh]\n\".zshrc\"\n";
+        let engine = engine_with(dir.path(), tree, config);
+        let dotfiles_dir = engine.dotfiles_dir().to_path_buf();
+        fs::create_dir_all(dotfiles_dir.join("etc/etc")).unwrap();
+        fs::write(dotfiles_dir.join("etc/etc/dotao-test-hosts"), "").unwrap();
+        fs::create_dir_all(dotfiles_dir.join("zsh")).unwrap();
+        fs::write(dotfiles_dir.join("zsh/.zshrc"), "").unwrap();
+
+        // Neither can be written to without privileges
+        fs::set_permissions(&target_root, fs::Permissions::from_mode(0o555)).unwrap();
+        let plan = engine.plan(ConflictStrategy::Skip).unwrap();
+        fs::set_permissions(&target_root, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(plan.links.iter().filter(|link| link.group == "zsh").all(|link| !link.privileged));
+        let commands = engine.privileged_commands(&plan);
+        if is_root() {
+            assert!(commands.is_empty());
+            return;
+        }
+        let location = Path::new("/etc/dotao-test-hosts");
+        let privileged: Vec<&PlannedLink> =
+            plan.links.iter().filter(|link| link.privileged).collect();
+        assert_eq!(privileged.len(), 1);
+        assert_eq!(
+            (privileged[0].group.as_str(), privileged[0].location.as_path()),
+            ("etc", location)
+        );
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0][..3], [OsString::from("doas"), "-n".into(), "ln".into()]);
+        // Blocked up front instead
+        fs::set_permissions(&target_root, fs::Permissions::from_mode(0o555)).unwrap();
+        let blockers = engine.preflight(&plan);
+        fs::set_permissions(&target_root, fs::Permissions::from_mode(0o755)).unwrap();
+        let blocker = Blocker { location: target_root.join(".zshrc"), kind: BlockerKind::Create };
+        assert_eq!(blockers[&target_root], [blocker]);
+    }
+
+    #[test]
+    fn dotfiles_folder_of_links() {
+        let location = Path::new("/h/.config/app/file");
+        let source = Path::new("app/.config/app/file");
+        let target = Path::new("../../dotfiles/app/.config/app/file");
+        assert_eq!(dotfiles_dir_of_link(location, target, source), Some("/h/dotfiles".into()));
+        let target = Path::new("/old/dotfiles/app/.config/app/file");
+        assert_eq!(dotfiles_dir_of_link(location, target, source), Some("/old/dotfiles".into()));
+        // Not through any dotfiles folder
+        let target = Path::new("/usr/share/app/file");
+        assert_eq!(dotfiles_dir_of_link(location, target, source), None);
+    }
+
+    #[test]
+    fn links_are_rewritten_after_moving_the_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let target_root = || PartialConfig {
+            target_root: Some(dir.path().to_path_buf()),
+            ..PartialConfig::default()
+        };
+        let engine = engine_with(dir.path(), "- [zsh]\n\".zshrc\"\n\".zshenv\"\n", target_root());
+        fs::create_dir(engine.dotfiles_dir().join("zsh")).unwrap();
+        fs::write(engine.dotfiles_dir().join("zsh/.zshrc"), "").unwrap();
+        fs::write(engine.dotfiles_dir().join("zsh/.zshenv"), "").unwrap();
+        engine.apply(&engine.plan(ConflictStrategy::Abort).unwrap()).unwrap();
+        // Points outside of any dotfiles folder
+        fs::remove_file(dir.path().join(".zshenv")).unwrap();
+        symlink("/usr/share/zsh/zshenv", dir.path().join(".zshenv")).unwrap();
+
+        let moved = dir.path().join("moved");
+        fs::rename(engine.dotfiles_dir(), &moved).unwrap();
+        let engine = Engine::new(&moved, target_root().finish(&moved)).unwrap();
+        let old = dir.path().join("dotfiles");
+        assert_eq!(engine.previous_locations().unwrap(), [old.clone()].iter().cloned().collect());
+
+        let relinks = engine.relinks(&[]).unwrap();
+        assert_eq!(relinks, [Relink {
+            group: "zsh".to_string(),
+            location: dir.path().join(".zshrc"),
+            found: "dotfiles/zsh/.zshrc".into(),
+            target: "moved/zsh/.zshrc".into(),
+            reason: RelinkReason::Moved(old),
+        }]);
+
+        engine.relink(&relinks).unwrap();
+        let zshrc = fs::read_link(dir.path().join(".zshrc")).unwrap();
+        assert_eq!(zshrc, Path::new("moved/zsh/.zshrc"));
+        let zshenv = fs::read_link(dir.path().join(".zshenv")).unwrap();
+        assert_eq!(zshenv, Path::new("/usr/share/zsh/zshenv"));
+        assert!(engine.relinks(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_group_rename_is_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PartialConfig {
+            target_root: Some(dir.path().to_path_buf()),
+            ..PartialConfig::default()
+        };
+        let mut engine = engine_with(dir.path(), "- [zsh]\n\".zshrc\"\n", config);
+        let dotfiles_dir = engine.dotfiles_dir().to_path_buf();
+        fs::create_dir(dotfiles_dir.join("zsh")).unwrap();
+        fs::write(dotfiles_dir.join("zsh/.zshrc"), "").unwrap();
+        engine.apply(&engine.plan(ConflictStrategy::Abort).unwrap()).unwrap();
+        let tree_file = fs::read_to_string(dotfiles_dir.join(TREE_FILE_NAME)).unwrap();
+        let link = fs::read_link(dir.path().join(".zshrc")).unwrap();
+
+        // Where the tree file is written before replacing it
+        fs::create_dir(dotfiles_dir.join(".dotao.tsml.tmp")).unwrap();
+        assert!(engine.move_group("zsh", "shell").is_err());
+
+        assert_eq!(fs::read_to_string(dotfiles_dir.join(TREE_FILE_NAME)).unwrap(), tree_file);
+        assert!(dotfiles_dir.join("zsh/.zshrc").is_file());
+        assert!(!dotfiles_dir.join("shell").exists());
+        assert_eq!(fs::read_link(dir.path().join(".zshrc")).unwrap(), link);
+        assert!(engine.groups().map.contains_key("zsh"));
+    }
+
+    #[test]
+    fn files_older_than_their_source_are_only_reabsorbed_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PartialConfig {
+            target_root: Some(dir.path().to_path_buf()),
+            ..PartialConfig::default()
+        };
+        let engine = engine_with(dir.path(), "- [zsh]\n\".zshrc\"\n", config);
+        let source = engine.dotfiles_dir().join("zsh/.zshrc");
+        fs::create_dir(source.parent().unwrap()).unwrap();
+        fs::write(&source, "old").unwrap();
+        engine.apply(&engine.plan(ConflictStrategy::Abort).unwrap()).unwrap();
+
+        // Saved by renaming over the link, then the source got pulled
+        let location = dir.path().join(".zshrc");
+        fs::remove_file(&location).unwrap();
+        fs::write(&location, "saved").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::open(&location).unwrap().set_modified(an_hour_ago).unwrap();
+        fs::write(&source, "pulled").unwrap();
+
+        let diff = engine.status().unwrap();
+        assert!(diff.replaced_by_file.is_empty());
+        assert_eq!(diff.replaced_by_older_file.len(), 1);
+        let conflicts = engine.plan(ConflictStrategy::Overwrite).unwrap().conflicts;
+        assert_eq!(conflicts[0].kind, ConflictKind::ReplacedByOlderFile);
+
+        let (reabsorbed, skipped) = engine.reabsorb(&[]).unwrap();
+        assert!(reabsorbed.is_empty());
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "pulled");
+
+        let (reabsorbed, skipped) = engine.reabsorb(std::slice::from_ref(&location)).unwrap();
+        assert_eq!(reabsorbed.len(), 1);
+        assert!(skipped.is_empty());
+        assert_eq!(fs::read_to_string(&source).unwrap(), "saved");
+        assert!(fs::symlink_metadata(&location).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    fn links_of_variants_that_no_longer_match_are_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let facts = |os: &str| Facts { os: os.to_string(), arch: "x86_64".into(), host: "".into() };
+        let engine = engine_at(dir.path(), "- [app]\n\"conf##os.linux\"\n\"other\"\n")
+            .with_facts(facts("linux"));
+        fs::create_dir(engine.dotfiles_dir().join("app")).unwrap();
+        fs::write(engine.dotfiles_dir().join("app/conf##os.linux"), "").unwrap();
+        fs::write(engine.dotfiles_dir().join("app/other"), "").unwrap();
+        engine.apply(&engine.plan(ConflictStrategy::Abort).unwrap()).unwrap();
+        assert!(engine.status().unwrap().stale_variant.is_empty());
+
+        let engine = engine.with_facts(facts("macos"));
+        let diff = engine.status().unwrap();
+        let stale: Vec<&Path> =
+            diff.stale_variant.iter().map(|link| link.location.as_path()).collect();
+        assert_eq!(stale, [dir.path().join("conf")]);
+        assert_eq!(diff.stale_variant[0].file.path(), Path::new("conf##os.linux"));
+        assert_eq!(diff.linked_correctly.len(), 1);
+
+        // Left as it is by linking
+        assert!(engine.plan(ConflictStrategy::Overwrite).unwrap().is_empty());
+    }
+}
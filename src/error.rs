@@ -0,0 +1,222 @@
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
+
+use file_tree::FtError;
+use tsml::TsmlError;
+
+use crate::util::to_utf;
+
+/// Our `Result` type.
+pub type DotaoResult<T> = Result<T, DotaoError>;
+
+/// Everything that can go wrong, each kind has its own exit code, see [`DotaoError::exit_code`].
+#[derive(Debug)]
+pub enum DotaoError {
+    /// `operation` failed at `path`.
+    Io { operation: &'static str, path: PathBuf, source: io::Error },
+    /// Failed to parse or serialize the tree file at `path`.
+    Tsml { path: PathBuf, source: TsmlError },
+    /// The tree file parsed, but its content doesn't make sense.
+    TreeFile { path: PathBuf, message: String },
+    /// `operation` failed while reading a file structure.
+    FileTree { operation: &'static str, source: FtError },
+    /// Invalid config file, env var or flag.
+    Config(String),
+    /// No tree file at the current directory and its parents, or at the explicit dotfiles folder.
+    DotfilesDirNotFound { searched_from: PathBuf, searched_parents: bool },
+    /// Linking was prevented by conflicts.
+    Conflicts { amount: usize },
+    /// An external command (git, hooks) failed.
+    Command { command: String, reason: String },
+    /// The user refused a confirmation prompt.
+    Aborted(String),
+    /// The command can't run in the current state.
+    InvalidUsage { message: String, hint: String },
+    /// An operation failed with `cause`, and undoing its first steps failed too.
+    RollbackFailed { cause: Box<DotaoError>, rollback_error: Box<DotaoError> },
+    /// Linking was prevented by missing permissions, or files in place of directories, found
+    /// before touching anything.
+    Blocked { amount: usize },
+    /// Another run is changing the repository, `holder` describes it, if known.
+    Locked { holder: Option<String> },
+    /// Files in group folders are missing from the tree file, with `--fail-untracked`.
+    Untracked { amount: usize },
+}
+
+use DotaoError::*;
+
+impl DotaoError {
+    pub fn io(operation: &'static str, path: impl AsRef<Path>, source: io::Error) -> Self {
+        Io { operation, path: path.as_ref().to_path_buf(), source }
+    }
+
+    pub fn tsml(path: impl AsRef<Path>, source: TsmlError) -> Self {
+        Tsml { path: path.as_ref().to_path_buf(), source }
+    }
+
+    pub fn invalid_usage(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        InvalidUsage { message: message.into(), hint: hint.into() }
+    }
+
+    /// Process exit code for this error, `1` is left for clap's usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Io { .. } => 2,
+            Tsml { .. } | TreeFile { .. } => 3,
+            FileTree { .. } => 4,
+            Config(..) => 5,
+            DotfilesDirNotFound { .. } => 6,
+            Conflicts { .. } => 7,
+            Command { .. } => 8,
+            Aborted(..) => 9,
+            InvalidUsage { .. } => 10,
+            RollbackFailed { .. } => 11,
+            Blocked { .. } => 12,
+            Locked { .. } => 13,
+            Untracked { .. } => 14,
+        }
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> String {
+        match self {
+            Io { .. } => {
+                "check that the path exists and that you have permission to access it".to_string()
+            },
+            Tsml { source: TsmlError::UnsupportedVersion(_), .. } => {
+                "the tree file was written for a newer dotao, update dotao to read it".to_string()
+            },
+            Tsml { .. } | TreeFile { .. } => {
+                "fix the tree file, the syntax is described in the comments of a new one (see \
+                 `dotao init`)"
+                    .to_string()
+            },
+            FileTree { source, .. } => match source.path() {
+                Some(path) => format!("check the file structure at '{}'", to_utf(path)),
+                None => "check the file structure of your groups".to_string(),
+            },
+            Config(..) => "fix the config file, env var or flag mentioned above".to_string(),
+            DotfilesDirNotFound { .. } => "run `dotao init` to create one, or point to your \
+                                           dotfiles folder with `dotao -C <dir>` or the \
+                                           DOTAO_DIR env var"
+                .to_string(),
+            Conflicts { .. } => "solve the conflicts above manually, or run again with \
+                                 `--conflict skip` or `--conflict overwrite`"
+                .to_string(),
+            Command { .. } => "check the output of the command above".to_string(),
+            Aborted(..) => "run again and confirm to proceed".to_string(),
+            InvalidUsage { hint, .. } => hint.clone(),
+            RollbackFailed { .. } => "changes were left half done, check the dotfiles folder \
+                                      with `git status` and the links with `dotao list`"
+                .to_string(),
+            Blocked { .. } => "fix the permissions of the directories above (or move the files \
+                               in their place), or add their groups to `system_groups` in the \
+                               config"
+                .to_string(),
+            Locked { .. } => "wait for it to finish, then run again".to_string(),
+            Untracked { .. } => "track them with `dotao add <group>`, or remove them from the \
+                                 group folder"
+                .to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DotaoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Io { operation, path, source } => {
+                write!(f, "failed to {} '{}': {}", operation, to_utf(path), source)
+            },
+            Tsml { path, source } => write!(f, "invalid tree file '{}': {}", to_utf(path), source),
+            TreeFile { path, message } => {
+                write!(f, "invalid tree file '{}': {}", to_utf(path), message)
+            },
+            FileTree { operation, source } => match source.path() {
+                Some(path) => write!(f, "failed to {} '{}': {}", operation, to_utf(path), source),
+                None => write!(f, "failed to {}: {}", operation, source),
+            },
+            Config(message) => write!(f, "{}", message),
+            DotfilesDirNotFound { searched_from, searched_parents } => {
+                write!(f, "could not find 'dotao.tsml' in '{}'", to_utf(searched_from))?;
+                if *searched_parents {
+                    write!(f, " or any of its parents")?;
+                }
+                Ok(())
+            },
+            Conflicts { amount } => write!(f, "aborted, {} conflict(s) prevent linking", amount),
+            Command { command, reason } => write!(f, "command `{}` failed: {}", command, reason),
+            Aborted(message) => write!(f, "aborted, {}", message),
+            InvalidUsage { message, .. } => write!(f, "{}", message),
+            RollbackFailed { cause, rollback_error } => {
+                write!(f, "{}, and rolling back failed too: {}", cause, rollback_error)
+            },
+            Blocked { amount } => {
+                write!(f, "aborted, {} operation(s) would fail, see the blockers above", amount)
+            },
+            Locked { holder: Some(holder) } => {
+                write!(f, "the repository is being changed by {}", holder)
+            },
+            Locked { holder: None } => {
+                write!(f, "the repository is being changed by another dotao command")
+            },
+            Untracked { amount } => {
+                write!(f, "{} file(s) in group folders are missing from the tree file", amount)
+            },
+        }
+    }
+}
+
+impl error::Error for DotaoError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Io { source, .. } => Some(source),
+            Tsml { source, .. } => Some(source),
+            FileTree { source, .. } => Some(source),
+            RollbackFailed { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_and_hints() {
+        let io_error = || io::Error::from(io::ErrorKind::NotFound);
+        let errors = [
+            (DotaoError::io("read", "x", io_error()), 2),
+            (DotaoError::tsml("x", TsmlError::UnsupportedVersion("9".into())), 3),
+            (TreeFile { path: "x".into(), message: "bad".into() }, 3),
+            (FileTree { operation: "read", source: FtError::NotFoundError("x".into()) }, 4),
+            (Config("bad".into()), 5),
+            (DotfilesDirNotFound { searched_from: "x".into(), searched_parents: true }, 6),
+            (Conflicts { amount: 1 }, 7),
+            (Command { command: "git".into(), reason: "failed".into() }, 8),
+            (Aborted("refused".into()), 9),
+            (DotaoError::invalid_usage("bad", "do this instead"), 10),
+            (
+                RollbackFailed {
+                    cause: Box::new(Conflicts { amount: 1 }),
+                    rollback_error: Box::new(DotaoError::io("remove", "x", io_error())),
+                },
+                11,
+            ),
+            (Blocked { amount: 1 }, 12),
+            (Locked { holder: None }, 13),
+            (Untracked { amount: 1 }, 14),
+        ];
+        for (error, code) in errors.iter() {
+            assert_eq!(error.exit_code(), *code, "{:?}", error);
+            assert!(!error.hint().is_empty(), "{:?}", error);
+        }
+
+        assert_eq!(errors[9].0.hint(), "do this instead");
+        assert!(errors[1].0.hint().contains("update dotao"));
+        assert!(errors[2].0.hint().contains("fix the tree file"));
+        assert!(errors[3].0.hint().contains("'x'"));
+    }
+}
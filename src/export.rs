@@ -0,0 +1,273 @@
+//! Export the files of the active entries as they'd appear under the target root, without links.
+//!
+//! Used to ship the deployed result to machines without dotao, like container images.
+
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    os::unix::{self, fs::MetadataExt},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use permissions::PermissionBits;
+
+use crate::{
+    diff::has_active_tags,
+    engine::link_locations,
+    error::{DotaoError, DotaoResult},
+    util::{self, to_utf, TREE_FILE_NAME},
+    Engine,
+};
+
+/// Where [`export`] writes the files to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A tar archive.
+    Tar,
+    /// A staging directory, created if missing, must be empty otherwise.
+    Dir,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "tar" => Ok(Self::Tar),
+            "dir" => Ok(Self::Dir),
+            _ => Err(r#"one of "tar" or "dir""#.to_string()),
+        }
+    }
+}
+
+/// A file written by [`export`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedFile {
+    pub group: String,
+    /// Path of the source file, inside of the group folder.
+    pub source: PathBuf,
+    /// Path inside of the export, relative to the target root.
+    pub path: PathBuf,
+    pub mode: PermissionBits,
+    /// Target of symlink entries, they're exported as symlinks.
+    pub link_target: Option<PathBuf>,
+}
+
+/// What was done by [`export`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub files: Vec<ExportedFile>,
+    /// System groups, their files aren't under the target root.
+    pub skipped_groups: Vec<String>,
+}
+
+// Every file to export, with the mode in the tree file, or the one of the source
+fn collect_files(engine: &Engine) -> DotaoResult<ExportReport> {
+    let config = engine.config();
+    let alternates = engine.alternates();
+    let mut report = ExportReport::default();
+
+    for (group, trees) in engine.groups().map.iter() {
+        if config.is_system_group(group) {
+            report.skipped_groups.push(group.clone());
+            continue;
+        }
+        let files = trees.iter().flat_map(|tree| tree.files().skip_dirs(true));
+        let files = files.filter(|file| has_active_tags(file, &config.default_tags));
+        // Only the chosen variant of alternates
+        let files = files.filter(|file| !alternates.iter().any(|alt| alt.leaves_out(file)));
+        for file in files {
+            let source = engine.dotfiles_dir().join(group).join(file.path());
+            let link_target = match file {
+                tsml::FileTree::Symlink { target_path, .. } => Some(target_path.clone()),
+                _ => None,
+            };
+            let mode = match file.extra().as_ref().and_then(|tags| tags.mode) {
+                Some(mode) => mode,
+                None if link_target.is_some() => PermissionBits::from_bits(0o777),
+                // Follows symlinks, the content is what gets exported
+                None => fs::metadata(&source)
+                    .map(|metadata| PermissionBits::from(metadata.permissions()))
+                    .map_err(|err| DotaoError::io("read metadata of", &source, err))?,
+            };
+            // A copy for each alias too
+            for location in link_locations(file, group, config) {
+                // The parser rejects aliases that leave the target root, but the output directory
+                // must never be left
+                let path = location.strip_prefix(config.target_root_of(group)).map_err(|_| {
+                    DotaoError::TreeFile {
+                        path: engine.dotfiles_dir().join(TREE_FILE_NAME),
+                        message: format!(
+                            "'{}' of group '{}' is linked outside of the target root",
+                            to_utf(file.path()),
+                            group
+                        ),
+                    }
+                })?;
+                report.files.push(ExportedFile {
+                    group: group.clone(),
+                    source: source.clone(),
+                    path: path.to_path_buf(),
+                    mode,
+                    link_target: link_target.clone(),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+// Directories leading to the exported files, parents first
+fn parent_dirs(files: &[ExportedFile]) -> BTreeSet<PathBuf> {
+    files
+        .iter()
+        .flat_map(|file| file.path.ancestors().skip(1))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+// Header from `metadata`, without the owner, which means nothing on other machines
+fn anonymous_header(metadata: &fs::Metadata) -> io::Result<tar::Header> {
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(metadata);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("")?;
+    header.set_groupname("")?;
+    Ok(header)
+}
+
+fn write_tar(files: &[ExportedFile], output: &Path) -> DotaoResult<()> {
+    let write_error = |err| DotaoError::io("write", output, err);
+    let archive = fs::File::create(output).map_err(write_error)?;
+    let mut builder = tar::Builder::new(io::BufWriter::new(archive));
+
+    // Directories get the newest time of the files, so the archive only changes when they do
+    let mut mtime = 0;
+    let mut headers = vec![];
+    for file in files.iter() {
+        let metadata = fs::symlink_metadata(&file.source)
+            .map_err(|err| DotaoError::io("read metadata of", &file.source, err))?;
+        mtime = mtime.max(metadata.mtime().max(0) as u64);
+        headers.push((file, metadata));
+    }
+
+    for dir in parent_dirs(files) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_mtime(mtime);
+        header.set_size(0);
+        builder.append_data(&mut header, &dir, io::empty()).map_err(write_error)?;
+    }
+
+    for (file, metadata) in headers {
+        if let Some(target) = &file.link_target {
+            let mut header = anonymous_header(&metadata).map_err(write_error)?;
+            header.set_size(0);
+            builder.append_link(&mut header, &file.path, target).map_err(write_error)?;
+            continue;
+        }
+        let content = fs::File::open(&file.source)
+            .map_err(|err| DotaoError::io("read", &file.source, err))?;
+        let mut header = anonymous_header(&metadata).map_err(write_error)?;
+        header.set_mode(file.mode.bits() as u32);
+        builder.append_data(&mut header, &file.path, content).map_err(write_error)?;
+    }
+
+    let mut writer = builder.into_inner().map_err(write_error)?;
+    io::Write::flush(&mut writer).map_err(write_error)
+}
+
+fn write_dir(files: &[ExportedFile], output: &Path) -> DotaoResult<()> {
+    let is_empty = match fs::read_dir(output) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => true,
+        Err(err) => return Err(DotaoError::io("read", output, err)),
+    };
+    if !is_empty {
+        return Err(DotaoError::invalid_usage(
+            format!("the staging directory '{}' is not empty", to_utf(output)),
+            "remove it, or export to a new directory",
+        ));
+    }
+
+    fs::create_dir_all(output).map_err(|err| DotaoError::io("create directory", output, err))?;
+    for dir in parent_dirs(files) {
+        let dir = output.join(dir);
+        fs::create_dir_all(&dir).map_err(|err| DotaoError::io("create directory", &dir, err))?;
+    }
+
+    for file in files.iter() {
+        let destination = output.join(&file.path);
+        if let Some(target) = &file.link_target {
+            unix::fs::symlink(target, &destination)
+                .map_err(|err| DotaoError::io("create symlink at", &destination, err))?;
+            continue;
+        }
+        // Follows symlinks, copying the content
+        fs::copy(&file.source, &destination)
+            .map_err(|err| DotaoError::io("copy", &file.source, err))?;
+        util::set_mode(&destination, file.mode)?;
+    }
+    Ok(())
+}
+
+/// Write the files of the entries with active tags to `output`, laid out as they'd appear under
+/// the target root, with the content of the sources instead of links, and modes applied. Symlink
+/// entries stay symlinks.
+///
+/// System groups are skipped, their files aren't under the target root.
+pub fn export(engine: &Engine, format: ExportFormat, output: &Path) -> DotaoResult<ExportReport> {
+    let report = collect_files(engine)?;
+    match format {
+        ExportFormat::Tar => write_tar(&report.files, output)?,
+        ExportFormat::Dir => write_dir(&report.files, output)?,
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_dirs_are_sorted_and_unique() {
+        let file = |path: &str| ExportedFile {
+            group: "g".to_string(),
+            source: PathBuf::from("/d/g").join(path),
+            path: PathBuf::from(path),
+            mode: PermissionBits::from_bits(0o644),
+            link_target: None,
+        };
+        let files = [file(".config/nvim/init.lua"), file(".config/git/config"), file(".zshrc")];
+        let dirs: Vec<PathBuf> = parent_dirs(&files).into_iter().collect();
+        assert_eq!(dirs, [".config", ".config/git", ".config/nvim"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn tar_entries_have_no_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join(".zshrc");
+        fs::write(&source, "").unwrap();
+        let file = |path: &str, link_target: Option<&str>| ExportedFile {
+            group: "zsh".to_string(),
+            source: source.clone(),
+            path: PathBuf::from(path),
+            mode: PermissionBits::from_bits(0o644),
+            link_target: link_target.map(PathBuf::from),
+        };
+        let output = dir.path().join("export.tar");
+        write_tar(&[file(".zshrc", None), file(".zshenv", Some(".zshrc"))], &output).unwrap();
+
+        let mut archive = tar::Archive::new(fs::File::open(&output).unwrap());
+        for entry in archive.entries().unwrap() {
+            let header = entry.unwrap().header().clone();
+            assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+            assert_eq!(header.username().unwrap(), Some(""));
+            assert_eq!(header.groupname().unwrap(), Some(""));
+        }
+    }
+}
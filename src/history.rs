@@ -0,0 +1,643 @@
+//! Append-only log of the operations done by mutating commands, used by `dotao history` and
+//! `dotao undo`.
+//!
+//! The log lives at `.dotao/history.toml`, each operation is appended as a `[[operation]]` table,
+//! with its filesystem actions as `[[operation.action]]` tables. Files that are overwritten are
+//! copied to `.dotao/backups/<operation id>/` first, so they can be restored.
+//!
+//! Only what goes through a [`Recorder`] can be undone. `dotao init` (the tree file, the git
+//! setup) and the files copied into the new group by `dotao import-chezmoi` aren't recorded, the
+//! tree file changes of the import are.
+
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use permissions::PermissionBits;
+
+use crate::{
+    error::{DotaoError, DotaoResult},
+    util::{self, to_utf, LOCAL_STATE_DIR},
+};
+
+const HISTORY_FILE_NAME: &str = "history.toml";
+const BACKUPS_DIR_NAME: &str = "backups";
+
+pub fn history_location(dotfiles_dir: &Path) -> PathBuf {
+    dotfiles_dir.join(LOCAL_STATE_DIR).join(HISTORY_FILE_NAME)
+}
+
+fn backups_location(dotfiles_dir: &Path, id: u64) -> PathBuf {
+    dotfiles_dir.join(LOCAL_STATE_DIR).join(BACKUPS_DIR_NAME).join(id.to_string())
+}
+
+/// A single filesystem change, with what's needed to reverse it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// A symlink was created at `location`.
+    CreateLink { location: PathBuf, target: PathBuf },
+    /// The symlink at `location`, pointing to `target`, was removed.
+    RemoveLink { location: PathBuf, target: PathBuf },
+    /// The file at `location` was removed, a copy was kept at `backup`.
+    RemoveFile { location: PathBuf, backup: PathBuf },
+    /// The directory `from` was renamed to `to`.
+    RenameDir { from: PathBuf, to: PathBuf },
+    /// The file at `path` was written, with copies of its content before (if it existed) and
+    /// after.
+    WriteFile { path: PathBuf, before: Option<PathBuf>, after: PathBuf },
+    /// The mode of the file at `path` was changed.
+    SetMode { path: PathBuf, before: PermissionBits, after: PermissionBits },
+}
+
+/// A command that changed something, or an undo of previous operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub id: u64,
+    pub command: String,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub actions: Vec<Action>,
+    /// Ids of the operations reversed by this one, if it's an undo.
+    pub undoes: Vec<u64>,
+    /// Id of an operation that this undo stopped in the middle of, and how many of its last
+    /// actions were reversed, undoing it again resumes from there.
+    pub partially_undoes: Option<(u64, usize)>,
+}
+
+fn path_value(path: &Path) -> toml::Value {
+    toml::Value::String(path.to_string_lossy().into_owned())
+}
+
+fn mode_value(mode: PermissionBits) -> toml::Value {
+    toml::Value::String(format!("{:04o}", mode))
+}
+
+// If something can be created or removed at `path`
+fn is_creatable(path: &Path) -> bool {
+    permissions::is_creatable(path).unwrap_or(false)
+}
+
+impl Action {
+    // Paths changed when reversing it
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Action::CreateLink { location, .. }
+            | Action::RemoveLink { location, .. }
+            | Action::RemoveFile { location, .. } => vec![location],
+            Action::RenameDir { from, to } => vec![from, to],
+            Action::WriteFile { path, .. } | Action::SetMode { path, .. } => vec![path],
+        }
+    }
+
+    fn to_toml(&self) -> toml::Value {
+        let mut table = toml::value::Table::new();
+        let mut insert = |key: &str, value| {
+            table.insert(key.to_string(), value);
+        };
+        match self {
+            Action::CreateLink { location, target } => {
+                insert("kind", "create_link".into());
+                insert("location", path_value(location));
+                insert("target", path_value(target));
+            },
+            Action::RemoveLink { location, target } => {
+                insert("kind", "remove_link".into());
+                insert("location", path_value(location));
+                insert("target", path_value(target));
+            },
+            Action::RemoveFile { location, backup } => {
+                insert("kind", "remove_file".into());
+                insert("location", path_value(location));
+                insert("backup", path_value(backup));
+            },
+            Action::RenameDir { from, to } => {
+                insert("kind", "rename_dir".into());
+                insert("from", path_value(from));
+                insert("to", path_value(to));
+            },
+            Action::WriteFile { path, before, after } => {
+                insert("kind", "write_file".into());
+                insert("path", path_value(path));
+                if let Some(before) = before {
+                    insert("before", path_value(before));
+                }
+                insert("after", path_value(after));
+            },
+            Action::SetMode { path, before, after } => {
+                insert("kind", "set_mode".into());
+                insert("path", path_value(path));
+                insert("before", mode_value(*before));
+                insert("after", mode_value(*after));
+            },
+        }
+        toml::Value::Table(table)
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        let path = |key: &str| value.get(key)?.as_str().map(PathBuf::from);
+        let mode = |key: &str| tsml::parse_mode(value.get(key)?.as_str()?);
+        let action = match value.get("kind")?.as_str()? {
+            "create_link" => {
+                Action::CreateLink { location: path("location")?, target: path("target")? }
+            },
+            "remove_link" => {
+                Action::RemoveLink { location: path("location")?, target: path("target")? }
+            },
+            "remove_file" => {
+                Action::RemoveFile { location: path("location")?, backup: path("backup")? }
+            },
+            "rename_dir" => Action::RenameDir { from: path("from")?, to: path("to")? },
+            "write_file" => Action::WriteFile {
+                path: path("path")?,
+                before: path("before"),
+                after: path("after")?,
+            },
+            "set_mode" => Action::SetMode {
+                path: path("path")?,
+                before: mode("before")?,
+                after: mode("after")?,
+            },
+            _ => return None,
+        };
+        Some(action)
+    }
+}
+
+impl Operation {
+    /// If it reversed other operations, even if partially.
+    pub fn is_undo(&self) -> bool {
+        !self.undoes.is_empty() || self.partially_undoes.is_some()
+    }
+
+    fn to_toml(&self) -> toml::Value {
+        let mut table = toml::value::Table::new();
+        table.insert("id".to_string(), (self.id as i64).into());
+        table.insert("command".to_string(), self.command.clone().into());
+        table.insert("timestamp".to_string(), (self.timestamp as i64).into());
+        let undoes = self.undoes.iter().map(|id| toml::Value::from(*id as i64)).collect();
+        table.insert("undoes".to_string(), toml::Value::Array(undoes));
+        if let Some((id, reversed)) = self.partially_undoes {
+            let pair = vec![toml::Value::from(id as i64), toml::Value::from(reversed as i64)];
+            table.insert("partially_undoes".to_string(), toml::Value::Array(pair));
+        }
+        let actions = self.actions.iter().map(Action::to_toml).collect();
+        table.insert("action".to_string(), toml::Value::Array(actions));
+        toml::Value::Table(table)
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        let integer = |key: &str| value.get(key)?.as_integer().map(|number| number as u64);
+        let array = |key: &str| value.get(key).and_then(toml::Value::as_array).cloned();
+
+        Some(Self {
+            id: integer("id")?,
+            command: value.get("command")?.as_str()?.to_string(),
+            timestamp: integer("timestamp")?,
+            actions: array("action")
+                .unwrap_or_default()
+                .iter()
+                .map(Action::from_toml)
+                .collect::<Option<_>>()?,
+            undoes: array("undoes")
+                .unwrap_or_default()
+                .iter()
+                .map(|id| id.as_integer().map(|id| id as u64))
+                .collect::<Option<_>>()?,
+            partially_undoes: match array("partially_undoes").as_deref() {
+                Some([id, reversed]) => {
+                    Some((id.as_integer()? as u64, reversed.as_integer()? as usize))
+                },
+                Some(_) => return None,
+                None => None,
+            },
+        })
+    }
+}
+
+/// Every operation in the log, oldest first.
+pub fn load_history(dotfiles_dir: &Path) -> DotaoResult<Vec<Operation>> {
+    let path = history_location(dotfiles_dir);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let text = fs::read_to_string(&path).map_err(|err| DotaoError::io("read", &path, err))?;
+    let invalid = |message: String| DotaoError::InvalidUsage {
+        message: format!("the history at '{}' is corrupted: {}", to_utf(&path), message),
+        hint: "fix or remove the file, the history can't be used until then".to_string(),
+    };
+
+    let value = text.parse::<toml::Value>().map_err(|err| invalid(err.to_string()))?;
+    let operations = match value.get("operation") {
+        Some(operations) => operations.as_array().ok_or_else(|| invalid("bad format".into()))?,
+        None => return Ok(vec![]),
+    };
+    operations
+        .iter()
+        .map(|operation| {
+            Operation::from_toml(operation).ok_or_else(|| invalid("bad operation".into()))
+        })
+        .collect()
+}
+
+fn append_operation(dotfiles_dir: &Path, operation: &Operation) -> DotaoResult<()> {
+    let path = history_location(dotfiles_dir);
+    let mut document = toml::value::Table::new();
+    document.insert("operation".to_string(), toml::Value::Array(vec![operation.to_toml()]));
+    // Safe unwrap, made only of tables, strings and integers
+    let text = toml::to_string(&toml::Value::Table(document)).unwrap();
+
+    // Safe unwrap, the history is inside of a folder
+    let parent = path.parent().unwrap();
+    fs::create_dir_all(parent).map_err(|err| DotaoError::io("create directory", parent, err))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|err| DotaoError::io("open for writing", &path, err))?;
+    writeln!(file, "{}", text).map_err(|err| DotaoError::io("write to", &path, err))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+/// Records the actions of an operation in progress, appended to the log by `finish`.
+#[derive(Debug)]
+pub struct Recorder {
+    dotfiles_dir: PathBuf,
+    operation: Operation,
+}
+
+impl Recorder {
+    pub fn start(dotfiles_dir: &Path, command: &str) -> DotaoResult<Self> {
+        let id = load_history(dotfiles_dir)?.last().map_or(1, |last| last.id + 1);
+        let operation = Operation {
+            id,
+            command: command.to_string(),
+            timestamp: now(),
+            actions: vec![],
+            undoes: vec![],
+            partially_undoes: None,
+        };
+        Ok(Self { dotfiles_dir: dotfiles_dir.to_path_buf(), operation })
+    }
+
+    pub fn record(&mut self, action: Action) {
+        self.operation.actions.push(action);
+    }
+
+    /// Copy the file at `path` to the backups of this operation, returns where it was copied to.
+    pub fn backup(&mut self, path: &Path) -> DotaoResult<PathBuf> {
+        let dir = backups_location(&self.dotfiles_dir, self.operation.id);
+        fs::create_dir_all(&dir).map_err(|err| DotaoError::io("create directory", &dir, err))?;
+
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", fs::read_dir(&dir).map_or(0, Iterator::count)));
+        let backup = dir.join(name);
+        fs::copy(path, &backup).map_err(|err| DotaoError::io("back up", path, err))?;
+        Ok(backup)
+    }
+
+    /// Forget the operation, for when it was rolled back.
+    pub fn discard(self) {
+        let _ = fs::remove_dir_all(backups_location(&self.dotfiles_dir, self.operation.id));
+    }
+
+    /// Append the operation to the log, if anything was done.
+    pub fn finish(self) -> DotaoResult<()> {
+        if self.operation.actions.is_empty() && !self.operation.is_undo() {
+            return Ok(());
+        }
+        append_operation(&self.dotfiles_dir, &self.operation)
+    }
+}
+
+// What a path looks like, as far as undoing is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathState {
+    Missing,
+    Link(PathBuf),
+    Present,
+}
+
+// State of the paths, considering the actions that were already reversed
+struct View(HashMap<PathBuf, PathState>);
+
+impl View {
+    fn state(&self, path: &Path) -> PathState {
+        if let Some(state) = self.0.get(path) {
+            return state.clone();
+        }
+        match fs::symlink_metadata(path) {
+            Err(_) => PathState::Missing,
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                fs::read_link(path).map_or(PathState::Present, PathState::Link)
+            },
+            Ok(_) => PathState::Present,
+        }
+    }
+
+    // Check that `action` is still in place, and update the view as if it was reversed
+    fn reverse(&mut self, action: &Action) -> Result<(), String> {
+        let changed = |path: &Path| format!("'{}' changed since it ran", to_utf(path));
+        // Links of system groups need privileges, and undo doesn't take them
+        if let Some(path) = action.paths().into_iter().find(|path| !is_creatable(path)) {
+            return Err(format!("there's no permission to change '{}'", to_utf(path)));
+        }
+        match action {
+            Action::CreateLink { location, target } => {
+                if self.state(location) != PathState::Link(target.clone()) {
+                    return Err(changed(location));
+                }
+                self.0.insert(location.clone(), PathState::Missing);
+            },
+            Action::RemoveLink { location, target } => {
+                if self.state(location) != PathState::Missing {
+                    return Err(changed(location));
+                }
+                self.0.insert(location.clone(), PathState::Link(target.clone()));
+            },
+            Action::RemoveFile { location, .. } => {
+                if self.state(location) != PathState::Missing {
+                    return Err(changed(location));
+                }
+                self.0.insert(location.clone(), PathState::Present);
+            },
+            Action::RenameDir { from, to } => {
+                if self.state(to) != PathState::Present {
+                    return Err(changed(to));
+                }
+                if self.state(from) != PathState::Missing {
+                    return Err(changed(from));
+                }
+                self.0.insert(to.clone(), PathState::Missing);
+                self.0.insert(from.clone(), PathState::Present);
+            },
+            Action::WriteFile { path, after, .. } => {
+                // Only compare the content if it wasn't touched by a reversed action
+                if !self.0.contains_key(path) && fs::read(path).ok() != fs::read(after).ok() {
+                    return Err(changed(path));
+                }
+                self.0.insert(path.clone(), PathState::Present);
+            },
+            Action::SetMode { path, after, .. } => {
+                // Only compare the mode if it wasn't touched by a reversed action
+                let current = fs::metadata(path).map(|metadata| metadata.permissions().into());
+                if !self.0.contains_key(path) && current.ok() != Some(*after) {
+                    return Err(changed(path));
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn reverse_action(action: &Action) -> DotaoResult<()> {
+    match action {
+        Action::CreateLink { location, .. } => {
+            fs::remove_file(location).map_err(|err| DotaoError::io("remove", location, err))
+        },
+        Action::RemoveLink { location, target } => {
+            symlink(target, location).map_err(|err| DotaoError::io("create link at", location, err))
+        },
+        Action::RemoveFile { location, backup } => fs::copy(backup, location)
+            .map(drop)
+            .map_err(|err| DotaoError::io("restore", location, err)),
+        Action::RenameDir { from, to } => {
+            fs::rename(to, from).map_err(|err| DotaoError::io("rename", to, err))
+        },
+        Action::WriteFile { path, before: Some(before), .. } => {
+            fs::copy(before, path).map(drop).map_err(|err| DotaoError::io("restore", path, err))
+        },
+        Action::WriteFile { path, before: None, .. } => {
+            fs::remove_file(path).map_err(|err| DotaoError::io("remove", path, err))
+        },
+        Action::SetMode { path, before, .. } => util::set_mode(path, *before),
+    }
+}
+
+/// Operations that can still be undone, newest first, including the partially undone ones.
+pub fn undoable_operations(history: &[Operation]) -> Vec<&Operation> {
+    let undone: Vec<u64> = history.iter().flat_map(|operation| operation.undoes.clone()).collect();
+    history
+        .iter()
+        .rev()
+        .filter(|operation| !operation.is_undo() && !undone.contains(&operation.id))
+        .collect()
+}
+
+/// Actions of `operation` that weren't reversed yet, by partial undos in `history`.
+pub fn remaining_actions<'a>(history: &[Operation], operation: &'a Operation) -> &'a [Action] {
+    let reversed: usize = history
+        .iter()
+        .filter_map(|undo| undo.partially_undoes)
+        .filter(|(id, _)| *id == operation.id)
+        .map(|(_, reversed)| reversed)
+        .sum();
+    let actions = &operation.actions;
+    &actions[..actions.len().saturating_sub(reversed)]
+}
+
+/// Links that dotao created and didn't remove since, according to `history`, by location, with
+/// their targets. Undone operations are left out.
+pub fn recorded_links(history: &[Operation]) -> HashMap<PathBuf, PathBuf> {
+    let undone: Vec<u64> = history.iter().flat_map(|operation| operation.undoes.clone()).collect();
+    let mut links = HashMap::new();
+
+    let operations =
+        history.iter().filter(|operation| !operation.is_undo() && !undone.contains(&operation.id));
+    for action in operations.flat_map(|operation| remaining_actions(history, operation)) {
+        match action {
+            Action::CreateLink { location, target } => {
+                links.insert(location.clone(), target.clone());
+            },
+            Action::RemoveLink { location, .. } | Action::RemoveFile { location, .. } => {
+                links.remove(location);
+            },
+            _ => {},
+        }
+    }
+    links
+}
+
+/// Reverse the last `amount` operations, newest first, returns the ids of the reversed ones.
+///
+/// Each operation is checked before being reversed, if anything changed since it ran, undoing
+/// stops there. If reversing an action fails, the ones reversed before it are recorded, and
+/// undoing that operation again continues from the failed action.
+pub fn undo(dotfiles_dir: &Path, amount: usize, command: &str) -> DotaoResult<Vec<u64>> {
+    let history = load_history(dotfiles_dir)?;
+    let operations = undoable_operations(&history);
+    if operations.len() < amount {
+        return Err(DotaoError::invalid_usage(
+            format!("can't undo {} operation(s), there are only {}", amount, operations.len()),
+            "run `dotao history` to see them",
+        ));
+    }
+
+    let mut recorder = Recorder::start(dotfiles_dir, command)?;
+    let mut result = Ok(());
+
+    for operation in operations.into_iter().take(amount) {
+        let actions = remaining_actions(&history, operation);
+        let mut view = View(HashMap::new());
+        if let Err(change) = actions.iter().rev().try_for_each(|action| view.reverse(action)) {
+            result = Err(DotaoError::invalid_usage(
+                format!("refusing to undo operation #{}, {}", operation.id, change),
+                "undo it manually, `dotao history` shows what it did",
+            ));
+            break;
+        }
+
+        let mut reversed = 0;
+        result = actions.iter().rev().try_for_each(|action| {
+            reverse_action(action)?;
+            reversed += 1;
+            Ok(())
+        });
+        if result.is_err() {
+            if reversed > 0 {
+                recorder.operation.partially_undoes = Some((operation.id, reversed));
+            }
+            break;
+        }
+        recorder.operation.undoes.push(operation.id);
+    }
+
+    let undone = recorder.operation.undoes.clone();
+    recorder.finish()?;
+    result.map(|()| undone)
+}
+
+/// `timestamp` as "YYYY-MM-DD HH:MM:SS UTC".
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+
+    // Civil from days, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operation_round_trip() {
+        let operation = Operation {
+            id: 7,
+            command: "dotao link --conflict overwrite".to_string(),
+            timestamp: 1_700_000_000,
+            actions: vec![
+                Action::RemoveFile { location: "/h/.zshrc".into(), backup: "/b/.zshrc.0".into() },
+                Action::CreateLink { location: "/h/.zshrc".into(), target: "d/zsh/.zshrc".into() },
+                Action::WriteFile {
+                    path: "/d/dotao.tsml".into(),
+                    before: None,
+                    after: "/b/a".into(),
+                },
+                Action::SetMode {
+                    path: "/d/ssh/config".into(),
+                    before: PermissionBits::from_bits(0o644),
+                    after: PermissionBits::from_bits(0o600),
+                },
+            ],
+            undoes: vec![3],
+            partially_undoes: Some((2, 1)),
+        };
+        let mut document = toml::value::Table::new();
+        document.insert("operation".to_string(), toml::Value::Array(vec![operation.to_toml()]));
+        let text = toml::to_string(&toml::Value::Table(document)).unwrap();
+
+        let value = text.parse::<toml::Value>().unwrap();
+        let parsed = Operation::from_toml(&value["operation"][0]).unwrap();
+        assert_eq!(parsed, operation);
+    }
+
+    #[test]
+    fn links_of_undone_operations_are_left_out() {
+        let operation = |id, actions, undoes| Operation {
+            id,
+            command: "dotao link".to_string(),
+            timestamp: 0,
+            actions,
+            undoes,
+            partially_undoes: None,
+        };
+        let link = |location: &str| Action::CreateLink {
+            location: location.into(),
+            target: format!("d/zsh/{}", location).into(),
+        };
+        let history = [
+            operation(1, vec![link(".zshrc"), link(".zshenv")], vec![]),
+            operation(2, vec![link(".zprofile")], vec![]),
+            operation(3, vec![], vec![2]),
+            operation(
+                4,
+                vec![Action::RemoveLink { location: ".zshenv".into(), target: "x".into() }],
+                vec![],
+            ),
+        ];
+        let links = recorded_links(&history);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[Path::new(".zshrc")], Path::new("d/zsh/.zshrc"));
+    }
+
+    #[test]
+    fn partial_undos_resume() {
+        let link = |location: &str| Action::CreateLink {
+            location: location.into(),
+            target: format!("d/zsh/{}", location).into(),
+        };
+        let operation = |id, actions| Operation {
+            id,
+            command: "dotao link".to_string(),
+            timestamp: 0,
+            actions,
+            undoes: vec![],
+            partially_undoes: None,
+        };
+        let mut history = vec![
+            operation(1, vec![link(".zshrc"), link(".zshenv"), link(".zprofile")]),
+            operation(2, vec![]),
+        ];
+        history[1].partially_undoes = Some((1, 1));
+        history.push(Operation { id: 3, partially_undoes: Some((1, 1)), ..history[1].clone() });
+
+        assert_eq!(remaining_actions(&history, &history[0]), [link(".zshrc")]);
+        let undoable: Vec<u64> = undoable_operations(&history).iter().map(|op| op.id).collect();
+        assert_eq!(undoable, [1]);
+        let links = recorded_links(&history);
+        assert_eq!(links.keys().collect::<Vec<_>>(), [Path::new(".zshrc")]);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,360 @@
+//! Turn dotfiles managed by other tools into dotao groups.
+
+use std::{
+    fs,
+    os::unix,
+    path::{Path, PathBuf},
+};
+
+use permissions::PermissionBits;
+
+use crate::{
+    error::{DotaoError, DotaoResult},
+    util::{self, to_utf, LOCAL_STATE_DIR, STOW_LOCAL_IGNORE},
+    Engine,
+};
+
+/// What was done by [`import_stow`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StowImport {
+    /// Packages added as groups.
+    pub packages: Vec<String>,
+    /// Packages with their own ignore list, which isn't supported, the default one was used.
+    pub local_ignores: Vec<String>,
+}
+
+/// Names of the package folders inside of `stow_dir`, hidden folders (like `.git`) are skipped.
+pub fn stow_packages(stow_dir: &Path) -> DotaoResult<Vec<String>> {
+    let read_error = |err| DotaoError::io("read", stow_dir, err);
+    let mut packages = vec![];
+    for entry in fs::read_dir(stow_dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) if !name.starts_with('.') && name != LOCAL_STATE_DIR => name,
+            _ => continue,
+        };
+        if entry.file_type().map_err(read_error)?.is_dir() {
+            packages.push(name);
+        }
+    }
+    packages.sort();
+    Ok(packages)
+}
+
+/// Add each Stow package of the dotfiles folder of `engine` as a group, the files Stow ignores by
+/// default are skipped, see [`util::is_ignored`].
+///
+/// Links left by Stow already point to the sources, so they show up as linked correctly, even
+/// the folded ones (a link to a whole directory of the package).
+pub fn import_stow(engine: &mut Engine) -> DotaoResult<StowImport> {
+    let stow_dir = engine.dotfiles_dir().to_path_buf();
+    let packages = stow_packages(&stow_dir)?;
+    if packages.is_empty() {
+        return Err(DotaoError::invalid_usage(
+            format!("no Stow packages found in '{}'", to_utf(&stow_dir)),
+            "each package is a folder inside of the Stow directory",
+        ));
+    }
+
+    let local_ignores = packages
+        .iter()
+        .filter(|package| stow_dir.join(package).join(STOW_LOCAL_IGNORE).exists())
+        .cloned()
+        .collect();
+
+    let names: Vec<&str> = packages.iter().map(String::as_str).collect();
+    engine.add_groups(&names)?;
+    Ok(StowImport { packages, local_ignores })
+}
+
+// Holds the path of the source directory relative to the repository, if it's not its root
+const CHEZMOI_ROOT: &str = ".chezmoiroot";
+
+// Kept in the names of imported files, and set as the hidden prefix of the group
+const CHEZMOI_HIDDEN_PREFIX: &str = "dot_";
+
+// Prefixes of chezmoi source names, in no particular order, `dot_` and `literal_` end them
+const CHEZMOI_PREFIXES: [&str; 16] = [
+    "encrypted_",
+    "private_",
+    "readonly_",
+    "empty_",
+    "executable_",
+    "symlink_",
+    "create_",
+    "modify_",
+    "remove_",
+    "run_",
+    "once_",
+    "onchange_",
+    "before_",
+    "after_",
+    "exact_",
+    "external_",
+];
+
+/// Something of a chezmoi source that dotao can't express, see [`import_chezmoi`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Untranslated {
+    /// Path inside of the chezmoi source directory.
+    pub path: PathBuf,
+    pub reason: &'static str,
+    /// If it was imported anyway, without the feature.
+    pub imported: bool,
+}
+
+/// What was done by [`import_chezmoi`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChezmoiImport {
+    /// Amount of files (and symlinks) imported.
+    pub files: usize,
+    pub untranslated: Vec<Untranslated>,
+}
+
+// Attributes of a chezmoi source name
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SourceAttributes {
+    private: bool,
+    readonly: bool,
+    executable: bool,
+    symlink: bool,
+    template: bool,
+    // Prefixes without a dotao equivalent
+    unsupported: Vec<&'static str>,
+}
+
+// Split a chezmoi source name into the target name and its attributes
+fn parse_source_name(name: &str) -> (String, SourceAttributes) {
+    let mut attributes = SourceAttributes::default();
+    let mut rest = name;
+    let target = loop {
+        if let Some(literal) = rest.strip_prefix("literal_") {
+            break literal.to_string();
+        } else if let Some(hidden) = rest.strip_prefix("dot_") {
+            break format!(".{}", hidden);
+        }
+        let prefix = match CHEZMOI_PREFIXES.iter().find(|prefix| rest.starts_with(*prefix)) {
+            Some(prefix) => *prefix,
+            None => break rest.to_string(),
+        };
+        match prefix {
+            "private_" => attributes.private = true,
+            "readonly_" => attributes.readonly = true,
+            "executable_" => attributes.executable = true,
+            "symlink_" => attributes.symlink = true,
+            // Empty files are kept anyway
+            "empty_" => {},
+            unsupported => attributes.unsupported.push(unsupported),
+        }
+        rest = &rest[prefix.len()..];
+    };
+
+    let target = if let Some(literal) = target.strip_suffix(".literal") {
+        literal.to_string()
+    } else if let Some(template) = target.strip_suffix(".tmpl") {
+        attributes.template = true;
+        template.to_string()
+    } else {
+        target
+    };
+    (target, attributes)
+}
+
+// Why an entry with `attributes` can't be imported, if it can't
+fn skip_reason(attributes: &SourceAttributes) -> Option<&'static str> {
+    if attributes.template {
+        return Some("templates aren't supported");
+    }
+    attributes.unsupported.iter().find_map(|prefix| match *prefix {
+        "encrypted_" => Some("encrypted files aren't supported"),
+        "modify_" => Some("modify scripts aren't supported"),
+        "remove_" => Some("removing targets isn't supported"),
+        "run_" | "once_" | "onchange_" | "before_" | "after_" => {
+            Some("scripts aren't supported, run them from a hook instead")
+        },
+        "external_" => Some("externals aren't supported"),
+        _ => None,
+    })
+}
+
+// What an imported entry with `attributes` loses
+fn import_notes(attributes: &SourceAttributes, is_dir: bool) -> Vec<&'static str> {
+    let mut notes = vec![];
+    if attributes.unsupported.contains(&"create_") {
+        notes.push("files only created when missing aren't supported, it's linked like the others");
+    }
+    if attributes.unsupported.contains(&"exact_") {
+        notes.push("exact directories aren't supported, extra files in the target are kept");
+    }
+    if is_dir && (attributes.private || attributes.readonly) {
+        notes.push("modes of directories aren't recorded");
+    }
+    notes
+}
+
+// Copy the chezmoi source directory `source` into `destination`, recursively, with the real names
+// except for hidden ones, which keep the `dot_` prefix
+fn import_chezmoi_dir(
+    source: &Path,
+    destination: &Path,
+    relative: &Path,
+    report: &mut ChezmoiImport,
+) -> DotaoResult<()> {
+    let read_error = |err| DotaoError::io("read", source, err);
+    let mut entries = fs::read_dir(source)
+        .map_err(read_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_error)?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = relative.join(&name);
+        let untranslated = |reason, imported| Untranslated { path: path.clone(), reason, imported };
+
+        // chezmoi ignores hidden files, except for its own, which dotao can't translate
+        if name.starts_with('.') {
+            if name.starts_with(".chezmoi") {
+                let reason = "chezmoi's special files aren't supported";
+                report.untranslated.push(untranslated(reason, false));
+            }
+            continue;
+        }
+
+        let (target_name, attributes) = parse_source_name(&name);
+        if let Some(reason) = skip_reason(&attributes) {
+            report.untranslated.push(untranslated(reason, false));
+            continue;
+        }
+        // Like `literal_dot_git`, the hidden prefix of the group would turn it into a dot
+        if target_name.starts_with(CHEZMOI_HIDDEN_PREFIX) {
+            let reason = "names starting with `dot_` that aren't hidden aren't supported";
+            report.untranslated.push(untranslated(reason, false));
+            continue;
+        }
+        let source_name = match target_name.strip_prefix('.') {
+            Some(hidden) => format!("{}{}", CHEZMOI_HIDDEN_PREFIX, hidden),
+            None => target_name,
+        };
+        let is_dir = entry.path().is_dir() && !attributes.symlink;
+        for reason in import_notes(&attributes, is_dir) {
+            report.untranslated.push(untranslated(reason, true));
+        }
+
+        let target = destination.join(&source_name);
+        if is_dir {
+            fs::create_dir_all(&target)
+                .map_err(|err| DotaoError::io("create directory", &target, err))?;
+            import_chezmoi_dir(&entry.path(), &target, &path, report)?;
+        } else if attributes.symlink {
+            // The content is the target
+            let link_target = fs::read_to_string(entry.path())
+                .map_err(|err| DotaoError::io("read", entry.path(), err))?;
+            unix::fs::symlink(link_target.trim_end(), &target)
+                .map_err(|err| DotaoError::io("create symlink at", &target, err))?;
+            report.files += 1;
+        } else {
+            fs::copy(entry.path(), &target).map_err(|err| DotaoError::io("copy", &target, err))?;
+            let mut mode = if attributes.executable { 0o755 } else { 0o644 };
+            if attributes.private {
+                mode &= 0o700;
+            }
+            if attributes.readonly {
+                mode &= !0o222;
+            }
+            util::set_mode(&target, PermissionBits::from_bits(mode))?;
+            report.files += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Copy the chezmoi source directory at `source_dir` into a new `group` of the dotfiles folder of
+/// `engine`, renaming files to their target names, except for the `dot_` prefix, which is kept and
+/// set as the hidden prefix of the group, so the files aren't hidden in the group folder.
+///
+/// The `private_`, `readonly_` and `executable_` attributes become modes (recorded in the tree
+/// file, except for the executable bit alone, which git keeps), and `symlink_` entries become
+/// symlinks in the group folder, recorded as symlink entries. Everything else that has no dotao
+/// equivalent is reported, and skipped unless it makes sense without it.
+pub fn import_chezmoi(
+    engine: &mut Engine,
+    source_dir: &Path,
+    group: &str,
+) -> DotaoResult<ChezmoiImport> {
+    let group_dir = engine.dotfiles_dir().join(group);
+    if engine.groups().map.contains_key(group) || fs::symlink_metadata(&group_dir).is_ok() {
+        return Err(DotaoError::invalid_usage(
+            format!("group '{}' already exists", group),
+            "import into a new group",
+        ));
+    }
+
+    // Like chezmoi does, the source directory can be a folder of the repository
+    let root_file = source_dir.join(CHEZMOI_ROOT);
+    let source_dir = match fs::read_to_string(&root_file) {
+        Ok(root) => source_dir.join(root.trim()),
+        Err(_) => source_dir.to_path_buf(),
+    };
+    if !source_dir.is_dir() {
+        return Err(DotaoError::invalid_usage(
+            format!("'{}' is not a chezmoi source directory", to_utf(&source_dir)),
+            "it's usually at '~/.local/share/chezmoi', see `chezmoi source-path`",
+        ));
+    }
+
+    fs::create_dir_all(&group_dir)
+        .map_err(|err| DotaoError::io("create directory", &group_dir, err))?;
+    let mut report = ChezmoiImport::default();
+    import_chezmoi_dir(&source_dir, &group_dir, Path::new(""), &mut report)?;
+    engine.set_hidden_prefix(group, CHEZMOI_HIDDEN_PREFIX);
+    engine.add_groups(&[group])?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PartialConfig;
+
+    #[test]
+    fn stow_package_left_without_untracked_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let stow_dir = dir.path().join("dotfiles");
+        for file in ["zsh/.zshrc", "zsh/README.md", "zsh/LICENSE", "zsh/.gitignore", "zsh/.zshrc~"]
+        {
+            let path = stow_dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::write(stow_dir.join(util::TREE_FILE_NAME), "# tsml-version: 2\n").unwrap();
+        let config = PartialConfig::default().finish(&stow_dir);
+        let mut engine = Engine::new(stow_dir, config).unwrap();
+
+        let report = import_stow(&mut engine).unwrap();
+        assert_eq!(report.packages, ["zsh"]);
+        let files: Vec<_> = engine.groups().map["zsh"].iter().map(|file| file.path()).collect();
+        assert_eq!(files, [Path::new(".zshrc")]);
+        assert!(engine.untracked().unwrap().is_empty());
+    }
+
+    #[test]
+    fn chezmoi_source_names() {
+        let (name, attributes) = parse_source_name("private_executable_dot_local");
+        assert_eq!(name, ".local");
+        assert!(attributes.private && attributes.executable && !attributes.readonly);
+
+        assert_eq!(parse_source_name("dot_private_file").0, ".private_file");
+        assert_eq!(parse_source_name("literal_dot_git").0, "dot_git");
+        assert!(parse_source_name("symlink_dot_vimrc").1.symlink);
+
+        let (name, attributes) = parse_source_name("run_once_before_install.sh.tmpl");
+        assert_eq!(name, "install.sh");
+        assert!(attributes.template);
+        assert_eq!(attributes.unsupported, ["run_", "once_", "before_"]);
+        assert!(skip_reason(&attributes).is_some());
+
+        assert_eq!(parse_source_name("dot_zshrc.literal").0, ".zshrc");
+        assert!(skip_reason(&parse_source_name("create_dot_hushlogin").1).is_none());
+    }
+}
@@ -0,0 +1,36 @@
+//! Dotfiles manager inspired by Stow, as a library.
+//!
+//! The `dotao` binary is a thin wrapper around [`Engine`], use it to drive dotao from your own
+//! tools:
+//!
+//! ```no_run
+//! use dotao::{config::ConflictStrategy, Engine, PartialConfig};
+//!
+//! let engine = Engine::load("/home/user/dotfiles", PartialConfig::default())?;
+//! let plan = engine.plan(ConflictStrategy::Skip)?;
+//! let report = engine.apply(&plan)?;
+//! println!("linked {} file(s)", report.linked.len());
+//! # Ok::<(), dotao::DotaoError>(())
+//! ```
+
+pub mod alternates;
+pub mod config;
+pub mod diff;
+pub mod engine;
+pub mod error;
+pub mod export;
+pub mod history;
+pub mod import;
+pub mod lock;
+pub mod util;
+
+pub use self::{
+    alternates::Facts,
+    config::{Config, PartialConfig},
+    diff::{EntryState, StatusDiff},
+    engine::{
+        ApplyReport, Blocker, BlockerKind, Conflict, ConflictKind, Engine, Owner, Plan,
+        PlannedLink, PlannedMode, Reabsorbed, Relink, RelinkReason,
+    },
+    error::{DotaoError, DotaoResult},
+};
@@ -0,0 +1,183 @@
+//! Advisory lock on the dotfiles folder, so concurrent runs don't race on the tree file.
+//!
+//! Commands that change the repository take the exclusive lock with [`RepoLock::exclusive`],
+//! read-only ones take the shared lock with [`RepoLock::shared`]. The lock is a `flock` on a file
+//! inside of the local state directory, the kernel releases it when the process dies, so a crash
+//! can't leave the repository locked.
+//!
+//! The holder of the exclusive lock writes its pid and command line to the file, and clears it when
+//! done, if the next run finds it filled, the previous one crashed while changing the repository.
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{
+    error::{DotaoError, DotaoResult},
+    util::LOCAL_STATE_DIR,
+};
+
+// Inside of the local state directory
+const LOCK_FILE_NAME: &str = "lock";
+
+fn lock_file_path(dotfiles_dir: &Path) -> PathBuf {
+    dotfiles_dir.join(LOCAL_STATE_DIR).join(LOCK_FILE_NAME)
+}
+
+/// The process that holds (or held) the exclusive lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    /// How it was called, like `dotao add zsh`.
+    pub command: String,
+}
+
+impl Holder {
+    // From the content of the lock file, `None` if empty or not written by us
+    fn parse(content: &str) -> Option<Self> {
+        let mut lines = content.lines();
+        let pid = lines.next()?.parse().ok()?;
+        let command = lines.next()?.to_string();
+        Some(Self { pid, command })
+    }
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` (pid {})", self.command, self.pid)
+    }
+}
+
+/// A held lock on the dotfiles folder, released on drop.
+#[derive(Debug)]
+pub struct RepoLock {
+    file: File,
+    path: PathBuf,
+    exclusive: bool,
+    stale: Option<Holder>,
+}
+
+impl RepoLock {
+    /// Take the exclusive lock, for commands that change the repository.
+    ///
+    /// If another run holds it, calls `on_wait` with it (if known) and waits for it to finish.
+    pub fn exclusive(
+        dotfiles_dir: &Path,
+        command: &str,
+        on_wait: impl FnOnce(Option<&Holder>),
+    ) -> DotaoResult<Self> {
+        let path = lock_file_path(dotfiles_dir);
+        // Safe unwrap, it's inside of the dotfiles folder
+        let parent = path.parent().unwrap();
+        fs::create_dir_all(parent)
+            .map_err(|err| DotaoError::io("create directory", parent, err))?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            // Holds the last holder, read before replacing it
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| DotaoError::io("open", &path, err))?;
+
+        let mut lock = Self { file, path, exclusive: true, stale: None };
+        if !lock.flock(libc::LOCK_EX | libc::LOCK_NB)? {
+            on_wait(lock.read_holder()?.as_ref());
+            lock.flock(libc::LOCK_EX)?;
+        }
+
+        // Whoever filled it didn't get to clear it
+        lock.stale = lock.read_holder()?;
+        lock.write_holder(&Holder { pid: process::id(), command: command.to_string() })?;
+        Ok(lock)
+    }
+
+    /// Take the shared lock, for commands that only read the repository, fails with
+    /// [`DotaoError::Locked`] if a command that changes it is running.
+    ///
+    /// Returns `None` if nothing ever locked this folder.
+    pub fn shared(dotfiles_dir: &Path) -> DotaoResult<Option<Self>> {
+        let path = lock_file_path(dotfiles_dir);
+        // Read-only, this command might not be allowed to write here
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(DotaoError::io("open", &path, err)),
+        };
+
+        let mut lock = Self { file, path, exclusive: false, stale: None };
+        if !lock.flock(libc::LOCK_SH | libc::LOCK_NB)? {
+            let holder = lock.read_holder()?.map(|holder| holder.to_string());
+            return Err(DotaoError::Locked { holder });
+        }
+        lock.stale = lock.read_holder()?;
+        Ok(Some(lock))
+    }
+
+    /// The run that crashed while holding the exclusive lock, if the last one did.
+    pub fn stale(&self) -> Option<&Holder> {
+        self.stale.as_ref()
+    }
+
+    // Returns false if it would block, only possible with `LOCK_NB`
+    fn flock(&self, operation: libc::c_int) -> DotaoResult<bool> {
+        loop {
+            if unsafe { libc::flock(self.file.as_raw_fd(), operation) } == 0 {
+                return Ok(true);
+            }
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::WouldBlock => return Ok(false),
+                io::ErrorKind::Interrupted => continue,
+                _ => return Err(DotaoError::io("lock", &self.path, err)),
+            }
+        }
+    }
+
+    fn read_holder(&mut self) -> DotaoResult<Option<Holder>> {
+        let mut content = String::new();
+        self.file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| self.file.read_to_string(&mut content))
+            .map_err(|err| DotaoError::io("read", &self.path, err))?;
+        Ok(Holder::parse(&content))
+    }
+
+    fn write_holder(&mut self, holder: &Holder) -> DotaoResult<()> {
+        let content = format!("{}\n{}\n", holder.pid, holder.command);
+        self.file
+            .set_len(0)
+            .and_then(|_| self.file.seek(SeekFrom::Start(0)))
+            .and_then(|_| self.file.write_all(content.as_bytes()))
+            .map_err(|err| DotaoError::io("write", &self.path, err))
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        // Finished cleanly, closing the file releases the lock
+        if self.exclusive {
+            let _ = self.file.set_len(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holder_round_trip() {
+        let holder = Holder { pid: 42, command: "dotao add zsh".to_string() };
+        let content = format!("{}\n{}\n", holder.pid, holder.command);
+        assert_eq!(Holder::parse(&content), Some(holder));
+        assert_eq!(Holder::parse(""), None);
+        assert_eq!(Holder::parse("not a pid\n"), None);
+    }
+}
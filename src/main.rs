@@ -1,11 +1,13 @@
-#![feature(bool_to_option)]
 mod app;
 mod cli;
 mod commands;
-mod diff;
-mod macros;
-mod util;
+
+use std::process;
 
 fn main() {
-    app::run_app();
+    if let Err(err) = app::run_app() {
+        eprintln!("Error: {}.", err);
+        eprintln!("Hint: {}.", err.hint());
+        process::exit(err.exit_code());
+    }
 }
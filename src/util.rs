@@ -1,34 +1,145 @@
 // Should the functions in here use lazy_static?
 use std::{
     env,
-    ffi::{CStr, OsStr},
-    fs, mem,
-    os::unix::ffi::OsStrExt,
-    path::{Path, PathBuf},
+    ffi::{CStr, OsStr, OsString},
+    fs,
+    io::{self, BufRead, Write},
+    iter, mem,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Component, Path, PathBuf},
     ptr,
 };
 
 use libc::{self, c_char};
+use permissions::PermissionBits;
 
-use crate::error;
+use crate::error::{DotaoError, DotaoResult};
 
-pub fn load_groups_from_path(_path: impl AsRef<Path>) -> tsml::Groups {
-    let mut groups = tsml::Groups::from_path("dotao.tsml").unwrap();
+// Name of the tree file, its location defines the dotfiles folder
+pub const TREE_FILE_NAME: &str = "dotao.tsml";
+
+// Directory inside of the dotfiles folder with state that shouldn't be shared
+pub const LOCAL_STATE_DIR: &str = ".dotao";
+
+pub fn load_groups_from_path(path: impl AsRef<Path>) -> DotaoResult<tsml::Groups> {
+    let path = path.as_ref();
+    let mut groups =
+        tsml::Groups::from_path(path).map_err(|source| DotaoError::tsml(path, source))?;
     // Remove main group please
     if let Some(main_group) = groups.map.remove("main") {
         if !main_group.is_empty() {
-            error!("This should be empty");
+            return Err(DotaoError::TreeFile {
+                path: path.to_path_buf(),
+                message: "found entries before the first group".to_string(),
+            });
+        }
+    }
+    Ok(groups)
+}
+
+// Overwrite the tree file at `path`, keeping the comments at the top of it. It's written next to it
+// first and renamed over it, so a failure leaves it untouched
+pub fn write_groups_to_path(groups: &tsml::Groups, path: impl AsRef<Path>) -> DotaoResult<()> {
+    let path = path.as_ref();
+    // The header of the file is made of the starting comments and blank lines
+    let mut header = groups.info.file_header.clone();
+
+    let amount_of_trailing_empty = header.lines().rev().take_while(|line| line.is_empty()).count();
+    // Remove excessive empty lines
+    for _ in 0..amount_of_trailing_empty {
+        header.pop();
+    }
+
+    let tree_content =
+        tsml::groups_to_tsml(groups).map_err(|source| DotaoError::tsml(path, source))?;
+
+    let mut temporary_name = OsString::from(".");
+    temporary_name.push(path.file_name().unwrap_or_default());
+    temporary_name.push(".tmp");
+    let temporary_path = path.with_file_name(temporary_name);
+
+    let write_error = |source| DotaoError::io("write", &temporary_path, source);
+    let write = || -> DotaoResult<()> {
+        let file = fs::File::create(&temporary_path).map_err(write_error)?;
+        // Keep the mode of the file being replaced
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions()).map_err(write_error)?;
+        }
+        let mut writer = io::BufWriter::new(file);
+        for comment in header.lines() {
+            writeln!(writer, "{}", comment).map_err(write_error)?;
+        }
+        write!(writer, "{}", tree_content).map_err(write_error)?;
+        writer.flush().map_err(write_error)?;
+        fs::rename(&temporary_path, path).map_err(|err| DotaoError::io("write", path, err))
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&temporary_path);
+    })
+}
+
+// Walk up from `start` until a directory containing the tree file is found
+pub fn find_dotfiles_dir(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find(|dir| dir.join(TREE_FILE_NAME).is_file()).map(Path::to_path_buf)
+}
+
+// The dotfiles folder to operate on, `explicit` comes from `-C` or `DOTAO_DIR`, if not given,
+// search the current directory and its parents
+pub fn dotfiles_dir(explicit: Option<&str>) -> DotaoResult<PathBuf> {
+    let current_dir = current_dir()?;
+    let dir = match explicit {
+        Some(dir) => {
+            let dir = current_dir.join(dir);
+            if !dir.join(TREE_FILE_NAME).is_file() {
+                return Err(DotaoError::DotfilesDirNotFound {
+                    searched_from: dir,
+                    searched_parents: false,
+                });
+            }
+            dir
+        },
+        None => find_dotfiles_dir(&current_dir).ok_or(DotaoError::DotfilesDirNotFound {
+            searched_from: current_dir,
+            searched_parents: true,
+        })?,
+    };
+
+    dir.canonicalize().map_err(|source| DotaoError::io("resolve the path", &dir, source))
+}
+
+// Lexically resolve `.` and `..` components, without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push(component);
+                }
+            },
+            other => result.push(other),
         }
     }
-    groups
+    result
+}
+
+// Path that leads from directory `from` to `to`, both should be absolute and normalized
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path: PathBuf = iter::repeat_n(Component::ParentDir, from.len() - common).collect();
+    path.extend(&to[common..]);
+    path
 }
 
-pub fn current_dir() -> PathBuf {
-    env::current_dir()
-        .unwrap_or_else(|err| error!("Failed to read curent directory path: '{}'.", err))
+pub fn current_dir() -> DotaoResult<PathBuf> {
+    env::current_dir().map_err(|source| DotaoError::io("read", "the current directory", source))
 }
 
-pub fn home_dir() -> PathBuf {
+pub fn home_dir() -> DotaoResult<PathBuf> {
     unsafe fn char_ptr_to_path_buf(ptr: *mut c_char) -> PathBuf {
         OsStr::from_bytes(CStr::from_ptr(ptr).to_bytes()).into()
     }
@@ -58,63 +169,19 @@ pub fn home_dir() -> PathBuf {
                 None
             }
         })
-        .unwrap_or_else(|| error!("Unable to find HOME dir. Try setting the $HOME env var."))
-}
-
-// Opt for ~/.config/dotao/config.toml before ./config.toml
-pub fn config_location() -> Option<PathBuf> {
-    let local_config = current_dir().join("config.toml");
-    let home_config_location = home_dir().join(".config/dotao/config.toml");
-
-    local_config
-        .exists()
-        .then_some(local_config)
-        .or_else(|| home_config_location.exists().then_some(home_config_location))
-}
-
-// Always should return Some if config_location does also return Some
-pub fn load_config() -> Option<toml::Value> {
-    let config_path = config_location()?;
-    let text = fs::read_to_string(&config_path).unwrap_or_else(|err| {
-        error!("Error while trying to read config file at '{}': {}.", to_utf(config_path), err)
-    });
-
-    text.parse::<toml::Value>().unwrap_or_else(|err| {
-        error!("Error while trying to parse toml config file at 'config.toml': {}", err)
-    });
-    todo!();
-}
-
-fn toml_type_to_string(value: &toml::Value) -> String {
-    match value {
-        toml::Value::Array(..) => "Array",
-        toml::Value::Boolean(..) => "Boolean",
-        toml::Value::Datetime(..) => "Datetime",
-        toml::Value::Float(..) => "Float",
-        toml::Value::Integer(..) => "Integer",
-        toml::Value::String(..) => "String",
-        toml::Value::Table(..) => "Table",
-    }
-    .to_string()
-}
-
-pub fn backup_dir() -> PathBuf {
-    load_config()
-        .and_then(|config| {
-            config.get("backup_dir").map(|backup_dir_value| {
-                let backup_dir_value = backup_dir_value.as_str().unwrap_or_else(|| {
-                    error!(
-                        "Error: 'backup_dir' variable at '{}' should be of type String, instead, it's of type {}.",
-                        // Safe, cause load_config.is_some(), maybe rework this?
-                        to_utf(config_location().unwrap()),
-                        toml_type_to_string(backup_dir_value)
-                    )
-                });
-                PathBuf::from(backup_dir_value)
-
-            })
+        .ok_or_else(|| {
+            DotaoError::invalid_usage("unable to find HOME dir", "try setting the $HOME env var")
         })
-        .unwrap_or_else(|| PathBuf::from(".."))
+}
+
+// Replace a leading `~` with the HOME dir
+pub fn expand_tilde(path: &str) -> DotaoResult<PathBuf> {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            Ok(home_dir()?.join(rest.trim_start_matches('/')))
+        },
+        _ => Ok(PathBuf::from(path)),
+    }
 }
 
 pub fn to_utf(str: impl AsRef<OsStr>) -> String {
@@ -122,10 +189,9 @@ pub fn to_utf(str: impl AsRef<OsStr>) -> String {
     text.trim_matches('"').to_string()
 }
 
-fn _is_currently_in_git_repository() -> bool {
-    let current_dir = current_dir();
+pub fn is_in_git_repository(dir: &Path) -> bool {
     // Used to traverse
-    let mut path: &Path = &current_dir;
+    let mut path: &Path = dir;
     loop {
         if path.join(".git").exists() {
             return true;
@@ -137,10 +203,94 @@ fn _is_currently_in_git_repository() -> bool {
     }
 }
 
-pub fn is_in_dotfiles_folder() -> bool {
-    let current_dir = current_dir();
-    match current_dir.file_name() {
+// Prompt the user with a yes/no question, anything other than "y" or "yes" means no
+pub fn ask_for_confirmation(question: &str) -> DotaoResult<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush().map_err(|source| DotaoError::io("write", "stdout", source))?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|source| DotaoError::io("read answer from", "stdin", source))?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Stow reads ignore rules from this file, inside of each package.
+pub const STOW_LOCAL_IGNORE: &str = ".stow-local-ignore";
+
+/// If `path`, relative to the group folder, is left out of the group by `add` and not reported as
+/// untracked: editor temporary files and the files in Stow's default ignore list.
+pub fn is_ignored(path: &Path) -> bool {
+    is_editor_temporary_file(path) || is_ignored_by_stow(path)
+}
+
+// If Stow's default ignore list skips `path`, relative to the package folder
+fn is_ignored_by_stow(path: &Path) -> bool {
+    let name = match path.file_name().and_then(OsStr::to_str) {
+        Some(name) => name,
+        None => return false,
+    };
+    let is_top_level = path.parent() == Some(Path::new(""));
+
+    let version_control =
+        ["RCS", "CVS", ".cvsignore", ".svn", "_darcs", ".hg", ".git", ".gitignore", ".gitmodules"];
+    version_control.contains(&name)
+        || name == STOW_LOCAL_IGNORE
+        || (name.len() > 2 && name.ends_with(",v"))
+        || (name.len() > 2 && name.starts_with(".#"))
+        || (name.len() > 1 && name.ends_with('~'))
+        || (name.len() > 1 && name.starts_with('#') && name.ends_with('#'))
+        || (is_top_level
+            && (name.starts_with("README") || name.starts_with("LICENSE") || name == "COPYING"))
+}
+
+// Swap and backup files that editors leave next to the file being edited
+pub fn is_editor_temporary_file(path: &Path) -> bool {
+    let name = match path.file_name().and_then(OsStr::to_str) {
+        Some(name) => name,
+        None => return false,
+    };
+    [".swp", ".swo", ".swx", "~"].iter().any(|suffix| name.ends_with(suffix))
+        || name.starts_with(".#")
+        // Vim checks if it can write to a directory by creating this file
+        || name == "4913"
+}
+
+// Change the permission bits of the file at `path`, keeping the other bits of its mode
+pub fn set_mode(path: &Path, mode: PermissionBits) -> DotaoResult<()> {
+    let error = |err| DotaoError::io("change mode of", path, err);
+    let current = fs::metadata(path).map_err(error)?.permissions().mode();
+    let permissions = fs::Permissions::from_mode(current & !0o777 | mode.bits() as u32);
+    fs::set_permissions(path, permissions).map_err(error)
+}
+
+pub fn is_dotfiles_folder(dir: &Path) -> bool {
+    match dir.file_name() {
         None => false,
         Some(file_name) => file_name == Path::new("dotfiles"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stow_default_ignore_list() {
+        let ignored = [".git", "RCS", "a/.gitignore", "file,v", ".#lock", "backup~", "#autosave#"];
+        for path in ignored.iter() {
+            assert!(is_ignored_by_stow(Path::new(path)), "{}", path);
+        }
+        // Only at the top of the package
+        assert!(is_ignored_by_stow(Path::new("README.md")));
+        assert!(is_ignored_by_stow(Path::new("LICENSE")));
+        assert!(!is_ignored_by_stow(Path::new(".config/app/README.md")));
+
+        let kept = [".zshrc", ".config/nvim/init.lua", "~", "#"];
+        for path in kept.iter() {
+            assert!(!is_ignored_by_stow(Path::new(path)), "{}", path);
+        }
+    }
+}
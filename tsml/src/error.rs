@@ -1,6 +1,9 @@
 use std::{error, fmt, io, path::PathBuf};
 
-use crate::parser::{ParserErrorKind, TokenPosition};
+use crate::{
+    parser::{ParserErrorKind, TokenPosition},
+    version::{version_marker, CURRENT_VERSION},
+};
 
 #[derive(Debug)]
 pub enum TsmlError {
@@ -9,6 +12,8 @@ pub enum TsmlError {
     IoError(io::Error),
     PathWithoutName, // "we found a file that does not contain a name!"
     NonUtf8Path(PathBuf),
+    /// Version in the marker that this crate can't read.
+    UnsupportedVersion(String),
     Other,
 }
 
@@ -30,13 +35,19 @@ impl fmt::Display for TsmlError {
             TsmlError::NonUtf8Path(path) => {
                 write!(f, "found a path name that is invalid UTF-8: {}", path.to_string_lossy())
             },
+            TsmlError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "unsupported syntax version '{}', the latest supported is {}",
+                    version, CURRENT_VERSION
+                )
+            },
             TsmlError::Other => {
                 write!(f, "other error")
             },
             TsmlError::ParserError(position, kind) => {
                 write!(f, "parser error at {}:{}: ", position.line, position.column)?;
                 use ParserErrorKind::*;
-                eprintln!("asd");
                 // todo!("ajeita isso aqui");
                 match kind {
                     BracketUnclosed => {
@@ -57,6 +68,39 @@ impl fmt::Display for TsmlError {
                     TagAfterTag => {
                         write!(f, "tag after tag problemo")
                     },
+                    InvalidMode(mode) => {
+                        write!(f, "invalid mode '{}', expected octal like 0600", mode)
+                    },
+                    ModeOnGroup => {
+                        write!(f, "modes can't be set for a whole group, only for its entries")
+                    },
+                    InvalidAlias(alias) => {
+                        write!(
+                            f,
+                            "invalid alias '{}', expected a path relative to the target root, \
+                             without '.' or '..'",
+                            alias
+                        )
+                    },
+                    AliasOnGroup => {
+                        write!(f, "aliases can't be set for a whole group, only for its entries")
+                    },
+                    InvalidHiddenPrefix(prefix) => {
+                        write!(f, "invalid hidden prefix '{}', expected a name like dot_", prefix)
+                    },
+                    HiddenPrefixOnEntry => {
+                        write!(f, "hidden prefixes are set for a whole group, above its header")
+                    },
+                    SlashComment => {
+                        write!(f, "comments start with '#' since version 2 of the syntax")
+                    },
+                    HashComment => {
+                        write!(
+                            f,
+                            "'#' comments need the marker '{}' in the first line",
+                            version_marker(CURRENT_VERSION)
+                        )
+                    },
                 }
             },
         }
@@ -3,7 +3,12 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{lexer::run_lexer, parser::parse_tokens, GroupsMap, TsmlResult};
+use crate::{
+    lexer::run_lexer,
+    parser::parse_tokens,
+    version::{comment_prefix, detect_version},
+    GroupTagsMap, GroupsMap, HiddenPrefixMap, TsmlResult,
+};
 
 #[derive(Debug, Clone)]
 pub struct Groups {
@@ -12,16 +17,27 @@ pub struct Groups {
 }
 
 // Collect the start of the file to retrieve as GroupsInfo
-fn get_file_header(text: &str) -> String {
-    text.lines().take_while(|line| line.starts_with("//") || line.is_empty()).collect::<String>()
+fn get_file_header(text: &str, version: u32) -> String {
+    text.lines()
+        .take_while(|line| line.starts_with(comment_prefix(version)) || line.is_empty())
+        .map(|line| format!("{}\n", line))
+        .collect::<String>()
 }
 
 impl Groups {
     pub fn from_text(text: &str) -> TsmlResult<Self> {
-        let tokens = run_lexer(text);
-        parse_tokens(tokens, text).map(|(map, groups_order)| Groups {
+        let version = detect_version(text)?;
+        let tokens = run_lexer(text, version);
+        parse_tokens(tokens, text).map(|(map, groups_order, group_tags, hidden_prefixes)| Groups {
             map,
-            info: GroupsInfo { file_path: None, file_header: get_file_header(text), groups_order },
+            info: GroupsInfo {
+                file_path: None,
+                version,
+                file_header: get_file_header(text, version),
+                groups_order,
+                group_tags,
+                hidden_prefixes,
+            },
         })
     }
 
@@ -31,11 +47,54 @@ impl Groups {
         result.info.file_path = Some(path.as_ref().to_path_buf());
         Ok(result)
     }
+
+    /// Names of the groups in the order they should be written, the ones missing from
+    /// `groups_order` (added after parsing) come last.
+    pub fn ordered_names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> =
+            self.info.groups_order.iter().filter(|name| self.map.contains_key(*name)).collect();
+        names.extend(self.map.keys().filter(|name| !self.info.groups_order.contains(name)));
+        names
+    }
+
+    /// Rename group `old` to `new`, keeping its position and tags, returns false if `old` doesn't
+    /// exist or `new` already does.
+    pub fn rename_group(&mut self, old: &str, new: &str) -> bool {
+        if self.map.contains_key(new) {
+            return false;
+        }
+        let files = match self.map.remove(old) {
+            Some(files) => files,
+            None => return false,
+        };
+        self.map.insert(new.to_string(), files);
+
+        for name in self.info.groups_order.iter_mut().filter(|name| *name == old) {
+            *name = new.to_string();
+        }
+        if let Some(tags) = self.info.group_tags.remove(old) {
+            self.info.group_tags.insert(new.to_string(), tags);
+        }
+        if let Some(prefix) = self.info.hidden_prefixes.remove(old) {
+            self.info.hidden_prefixes.insert(new.to_string(), prefix);
+        }
+        true
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct GroupsInfo {
     pub file_path: Option<PathBuf>,
+    /// Version of the syntax the file was read with, it's kept when written back.
+    pub version: u32,
+    /// Comments and blank lines at the start of the file, the version marker included.
     pub file_header: String,
     pub groups_order: Vec<String>,
+    /// Tags written above each group header.
+    pub group_tags: GroupTagsMap,
+    /// Prefix written in place of the leading dot of names in each group folder, set with
+    /// `(hidden=dot_)` above the group header, see [`apply_hidden_prefix`].
+    ///
+    /// [`apply_hidden_prefix`]: crate::apply_hidden_prefix
+    pub hidden_prefixes: HiddenPrefixMap,
 }
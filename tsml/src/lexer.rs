@@ -2,6 +2,8 @@ use std::ops;
 
 use logos::Logos;
 
+use crate::version::UNVERSIONED;
+
 pub(crate) type SpannedLexToken = (LexToken, ops::Range<usize>);
 
 #[derive(Logos, Debug, PartialEq)]
@@ -16,7 +18,7 @@ pub enum LexToken {
         let start = lex.slice().find('[').unwrap();
         let end = lex.slice().rfind(']').unwrap();
 
-        let slice: &str = &lex.slice()[start + 1..end].trim();
+        let slice: &str = lex.slice()[start + 1..end].trim();
         String::from(slice)
     })]
     Group(String),
@@ -62,22 +64,36 @@ pub enum LexToken {
     })]
     Separator(char),
 
+    // Comments of version 1 of the syntax, start with two slashes
+    #[regex(r"//[^\n]*")]
+    SlashComment,
+
+    // Comments since version 2, start with a hash
+    #[regex(r"#[^\n]*")]
+    HashComment,
+
     // Ignore whitespace
     #[regex(r" *", logos::skip)]
     // Ignore tab
     #[regex(r"\t+", logos::skip)]
-    // TODO: switch to # comments
-    // Ignore comments, they start with two slashes
-    #[regex(r"//[^\n]*", logos::skip)]
     // Anything unexpected
     #[error]
     LexError,
 }
 
-pub fn run_lexer(input_text: &str) -> Vec<SpannedLexToken> {
+// Every token, comments included
+pub(crate) fn lex_with_comments(input_text: &str) -> Vec<SpannedLexToken> {
     LexToken::lexer(input_text).spanned().collect()
 }
 
+// Tokens of a file of `version`, without its comments, the comments of other versions are kept so
+// the parser can point at them
+pub fn run_lexer(input_text: &str, version: u32) -> Vec<SpannedLexToken> {
+    let comment =
+        if version == UNVERSIONED { LexToken::SlashComment } else { LexToken::HashComment };
+    lex_with_comments(input_text).into_iter().filter(|(token, _)| *token != comment).collect()
+}
+
 #[cfg(test)]
 mod lexer_tests {
     use super::{
@@ -104,14 +120,29 @@ mod lexer_tests {
         test(",", Separator(','));
     }
 
+    #[test]
+    fn comment_regex() {
+        test("// slashes", SlashComment);
+        test("# hash", HashComment);
+
+        let tokens = run_lexer("# header\n\"file\" // old\n", 2);
+        let tokens: Vec<LexToken> = tokens.into_iter().map(|(token, _)| token).collect();
+        assert_eq!(tokens, [
+            Separator('\n'),
+            Value(String::from("file")),
+            SlashComment,
+            Separator('\n')
+        ]);
+    }
+
     #[test]
     fn no_errors_check() {
         let files = ["examples/simplest.tree", "examples/simple.tree", "examples/dotao.tree"];
 
         for file in &files {
             let text = std::fs::read_to_string(file).unwrap();
-            let mut lex = LexToken::lexer(&text);
-            while let Some(token) = lex.next() {
+            let lex = LexToken::lexer(&text);
+            for token in lex {
                 assert!(!matches!(token, LexToken::LexError));
             }
         }
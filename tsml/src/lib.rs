@@ -4,16 +4,24 @@ mod lexer;
 mod parser;
 mod serializer;
 mod tags;
+mod version;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub use error::{TsmlError, TsmlResult};
 pub use groups::Groups;
 pub use lexer::LexToken;
-
-use crate::tags::Tags;
+pub use parser::declaration_lines;
+pub use tags::{apply_hidden_prefix, parse_mode, Tags};
+pub use version::{
+    comment_prefix, detect_version, migrate, version_marker, CURRENT_VERSION, UNVERSIONED,
+};
 pub type FileTree = file_tree::FileTree<Tags>;
 //
 pub type GroupsMap = BTreeMap<String, Vec<FileTree>>;
+/// Tags of each group, by group name.
+pub type GroupTagsMap = BTreeMap<String, BTreeSet<String>>;
+/// Hidden prefix of each group that has one, by group name.
+pub type HiddenPrefixMap = BTreeMap<String, String>;
 
 pub use serializer::groups_to_tsml;
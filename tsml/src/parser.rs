@@ -1,7 +1,14 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    path::PathBuf,
+};
 
 use crate::{
-    lexer::SpannedLexToken, tags::Tags, FileTree, GroupsMap, LexToken, TsmlError, TsmlResult,
+    lexer::{run_lexer, SpannedLexToken},
+    tags::{apply_hidden_prefix, parse_mode, Tags, ALIAS_PREFIX, HIDDEN_PREFIX, MODE_PREFIX},
+    version::{detect_version, CURRENT_VERSION},
+    FileTree, GroupTagsMap, GroupsMap, HiddenPrefixMap, LexToken, TsmlError, TsmlResult,
 };
 
 type Stack<T> = Vec<T>;
@@ -19,6 +26,16 @@ pub enum ParserErrorKind {
     CommasOutsideOfBrackets,
     MissingSymlinkTarget,
     TagAfterTag,
+    InvalidMode(String),
+    ModeOnGroup,
+    InvalidAlias(String),
+    AliasOnGroup,
+    InvalidHiddenPrefix(String),
+    HiddenPrefixOnEntry,
+    /// `//` comment in a file of version 2 or later.
+    SlashComment,
+    /// `#` comment in a file without the version marker.
+    HashComment,
 }
 
 fn update_map_group(map: &mut GroupsMap, group: String, files: &mut Stack<FileTree>) {
@@ -42,7 +59,7 @@ impl TokenPosition {
 pub fn parse_tokens(
     spanned_tokens: Vec<SpannedLexToken>,
     original_text: &str,
-) -> TsmlResult<(GroupsMap, Vec<String>)> {
+) -> TsmlResult<(GroupsMap, Vec<String>, GroupTagsMap, HiddenPrefixMap)> {
     let mut map = GroupsMap::new();
 
     let mut current_line = 1;
@@ -60,6 +77,13 @@ pub fn parse_tokens(
 
     let mut group_tags = Vec::<String>::new();
     let mut last_tags = Vec::<String>::new();
+    let mut last_mode = None;
+    let mut last_aliases = Vec::<PathBuf>::new();
+    let mut last_hidden_prefix = None;
+
+    // Kept apart from the entries, so empty groups don't lose their tags
+    let mut tags_of_groups = GroupTagsMap::new();
+    let mut hidden_prefixes = HiddenPrefixMap::new();
 
     let mut group_order = vec!["main".to_string()];
     let mut groups_seen = HashMap::<String, ()>::new();
@@ -80,20 +104,27 @@ pub fn parse_tokens(
                 read_state = ParserState::Busy;
                 already_read_some_lmao = true;
 
+                if last_hidden_prefix.is_some() {
+                    let kind = ParserErrorKind::HiddenPrefixOnEntry;
+                    return Err(TsmlError::ParserError(position, kind));
+                }
+
                 // Create tags and add every direct and group tags you've just seen
                 let mut tags = Tags::new();
                 last_tags.into_iter().for_each(|tag_name| {
                     tags.add_direct_tag(tag_name);
                 });
-                group_tags.into_iter().for_each(|tag_name| {
+                group_tags.iter().for_each(|tag_name| {
                     tags.add_group_tag(tag_name);
                 });
 
+                tags.mode = last_mode.take();
+                tags.aliases = mem::take(&mut last_aliases);
+
                 let mut file = FileTree::new_regular_with_extra(value, Some(tags));
 
-                // reinit for next iterations
+                // reinit for next iterations, group tags last until the next group
                 last_tags = vec![];
-                group_tags = vec![];
 
                 if let Some((LexToken::SymlinkArrow, _)) = tokens_iter.peek() {
                     if let Some((LexToken::Value(target), _)) = tokens_iter.nth(1) {
@@ -172,6 +203,13 @@ pub fn parse_tokens(
             },
 
             LexToken::Group(group) => {
+                if last_mode.is_some() {
+                    return Err(TsmlError::ParserError(position, ParserErrorKind::ModeOnGroup));
+                }
+                if !last_aliases.is_empty() {
+                    return Err(TsmlError::ParserError(position, ParserErrorKind::AliasOnGroup));
+                }
+
                 // This is intentionally mad bad
                 groups_seen.entry(group.to_string()).or_insert_with(|| {
                     group_order.push(group.clone());
@@ -182,9 +220,19 @@ pub fn parse_tokens(
                 // Update the group for the next entries
                 current_group = group.into();
 
+                if let Some(prefix) = last_hidden_prefix.take() {
+                    hidden_prefixes.insert(current_group.clone(), prefix);
+                }
+
                 // The last tags you've seen, are actually group_tags
                 group_tags = last_tags;
                 last_tags = vec![]; // reinit
+                if !group_tags.is_empty() {
+                    tags_of_groups
+                        .entry(current_group.clone())
+                        .or_default()
+                        .extend(group_tags.iter().cloned());
+                }
 
                 // After a group, we expect a line break
                 match tokens_iter.peek() {
@@ -197,10 +245,41 @@ pub fn parse_tokens(
             // doing this
             LexToken::Tags(tags) => {
                 // tags not clear yet to read more tags
-                if !last_tags.is_empty() {
+                if !last_tags.is_empty()
+                    || last_mode.is_some()
+                    || !last_aliases.is_empty()
+                    || last_hidden_prefix.is_some()
+                {
                     return Err(TsmlError::ParserError(position, ParserErrorKind::TagAfterTag));
                 }
-                last_tags = tags.clone();
+                for tag in tags.iter() {
+                    if let Some(mode) = tag.strip_prefix(MODE_PREFIX) {
+                        let bits = parse_mode(mode).ok_or_else(|| {
+                            let kind = ParserErrorKind::InvalidMode(mode.to_string());
+                            TsmlError::ParserError(position.clone(), kind)
+                        })?;
+                        last_mode = Some(bits);
+                    } else if let Some(alias) = tag.strip_prefix(ALIAS_PREFIX) {
+                        // Relative to the target root, like entries, and can't leave it
+                        let alias = alias.trim();
+                        let leaves_root = alias.split('/').any(|name| name == "." || name == "..");
+                        let alias = PathBuf::from(alias);
+                        if alias.as_os_str().is_empty() || alias.is_absolute() || leaves_root {
+                            let kind = ParserErrorKind::InvalidAlias(tag.clone());
+                            return Err(TsmlError::ParserError(position, kind));
+                        }
+                        last_aliases.push(alias);
+                    } else if let Some(prefix) = tag.strip_prefix(HIDDEN_PREFIX) {
+                        let prefix = prefix.trim();
+                        if prefix.is_empty() || prefix.contains('/') {
+                            let kind = ParserErrorKind::InvalidHiddenPrefix(tag.clone());
+                            return Err(TsmlError::ParserError(position, kind));
+                        }
+                        last_hidden_prefix = Some(prefix.to_string());
+                    } else {
+                        last_tags.push(tag.clone());
+                    }
+                }
             },
 
             // João Marcos!! Logos!! editar isso pls
@@ -208,6 +287,14 @@ pub fn parse_tokens(
                 unreachable!("Unexpected SymlinkArrow!");
             },
 
+            // The lexer only keeps the comments of other versions
+            LexToken::SlashComment => {
+                return Err(TsmlError::ParserError(position, ParserErrorKind::SlashComment));
+            },
+            LexToken::HashComment => {
+                return Err(TsmlError::ParserError(position, ParserErrorKind::HashComment));
+            },
+
             LexToken::LexError => {
                 eprintln!("LexError => '{}'", &original_text[range]);
             },
@@ -222,12 +309,19 @@ pub fn parse_tokens(
     }
 
     update_map_group(&mut map, current_group, &mut file_stack);
+    // Propagate paths and tags from parents to children
     fn propagate_to_children(ft: &mut FileTree, accumulated_path: &mut PathBuf) {
         let old_current: PathBuf = ft.path().clone();
         *ft.path_mut() = accumulated_path.join(ft.path());
         accumulated_path.push(old_current);
+        let parent_tags = ft.extra().clone();
         if let Some(children) = ft.children_mut() {
-            children.iter_mut().for_each(|x| propagate_to_children(x, accumulated_path));
+            children.iter_mut().for_each(|x| {
+                if let (Some(tags), Some(parent_tags)) = (x.extra_mut(), &parent_tags) {
+                    tags.inherit_from(parent_tags);
+                }
+                propagate_to_children(x, accumulated_path)
+            });
         }
         accumulated_path.pop();
     }
@@ -236,5 +330,159 @@ pub fn parse_tokens(
         propagate_to_children(ft, &mut PathBuf::new());
     }
 
-    Ok((map, group_order))
+    // Entries of groups with a hidden prefix are linked with the dots in place
+    for (group, prefix) in hidden_prefixes.iter() {
+        let set_target_path = |file: &mut FileTree| {
+            let target_path = apply_hidden_prefix(file.path(), prefix);
+            if target_path != *file.path() {
+                file.extra_mut().get_or_insert_with(Tags::new).target_path = Some(target_path);
+            }
+        };
+        for ft in map.get_mut(group).into_iter().flatten() {
+            ft.apply_to_all(set_target_path);
+        }
+    }
+
+    Ok((map, group_order, tags_of_groups, hidden_prefixes))
+}
+
+/// Line (starting at 1) where each entry is declared, by group name and entry path.
+///
+/// Meant for valid text, that already went through `parse_tokens`.
+pub fn declaration_lines(text: &str) -> BTreeMap<(String, PathBuf), usize> {
+    let version = detect_version(text).unwrap_or(CURRENT_VERSION);
+    let mut lines = BTreeMap::new();
+
+    let mut current_group = String::from("main");
+    // Directories we are inside of, and the last entry seen, which may be opened next
+    let mut parents = PathBuf::new();
+    let mut last_value = PathBuf::new();
+    let mut after_symlink_arrow = false;
+
+    let mut current_line = 1;
+    let mut counted_until = 0;
+
+    for (token, range) in run_lexer(text, version) {
+        current_line += text[counted_until..range.start].matches('\n').count();
+        counted_until = range.start;
+
+        match token {
+            LexToken::Group(group) => current_group = group,
+            LexToken::SymlinkArrow => after_symlink_arrow = true,
+            // Symlink targets aren't entries
+            LexToken::Value(_) if after_symlink_arrow => after_symlink_arrow = false,
+            LexToken::Value(value) => {
+                last_value = parents.join(value);
+                lines.insert((current_group.clone(), last_value.clone()), current_line);
+            },
+            LexToken::OpenBracket => parents = last_value.clone(),
+            LexToken::CloseBracket => {
+                parents.pop();
+            },
+            _ => {},
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod parser_tests {
+    use std::path::PathBuf;
+
+    use super::declaration_lines;
+    use crate::{FileTree, Groups};
+
+    #[test]
+    fn tags_propagation() {
+        let text =
+            "(laptop)\n- [i3]\n(linux) \".config\": [\n    \"i3\": [\"config\"]\n]\n\"other\"\n";
+        let groups = Groups::from_text(text).unwrap();
+        let files = &groups.map["i3"];
+
+        let config = files[0].files().last().unwrap();
+        let tags = config.extra().as_ref().unwrap();
+        assert!(tags.direct_tags.is_empty());
+        assert!(tags.ancestor_tags.contains("linux"));
+        assert_eq!(tags.effective_tags().len(), 2);
+
+        // Group tags apply to every entry of the group, not only the first one
+        let other = files[1].extra().as_ref().unwrap();
+        assert!(other.group_tags.contains("laptop"));
+        assert!(!other.ancestor_tags.contains("linux"));
+    }
+
+    #[test]
+    fn modes() {
+        let text =
+            "- [ssh]\n\".ssh\": [\n    (work, mode=0600) \"config\"\n    \"known_hosts\"\n]\n";
+        let groups = Groups::from_text(text).unwrap();
+        let files: Vec<_> = groups.map["ssh"][0].files().collect();
+
+        let config = files[1].extra().as_ref().unwrap();
+        assert_eq!(config.mode.map(|mode| mode.bits()), Some(0o600));
+        assert_eq!(config.effective_tags().len(), 1);
+        // Not inherited, and not a tag
+        assert_eq!(files[2].extra().as_ref().unwrap().mode, None);
+
+        assert!(Groups::from_text("(mode=0800) \"file\"\n").is_err());
+        assert!(Groups::from_text("(mode=0700)\n- [ssh]\n").is_err());
+    }
+
+    #[test]
+    fn aliases() {
+        let text = "- [editor]\n(work, alias=.vim/.editorconfig, alias=.config/x/editorconfig) \
+                    \"editorconfig\"\n\"other\"\n";
+        let groups = Groups::from_text(text).unwrap();
+        let files = &groups.map["editor"];
+
+        let tags = files[0].extra().as_ref().unwrap();
+        let aliases = [".vim/.editorconfig", ".config/x/editorconfig"].map(PathBuf::from);
+        assert_eq!(tags.aliases, aliases);
+        assert_eq!(tags.effective_tags().len(), 1);
+        assert!(files[1].extra().as_ref().unwrap().aliases.is_empty());
+
+        assert!(Groups::from_text("(alias=) \"file\"\n").is_err());
+        assert!(Groups::from_text("(alias=/etc/file) \"file\"\n").is_err());
+        assert!(Groups::from_text("(alias=../../etc/file) \"file\"\n").is_err());
+        assert!(Groups::from_text("(alias=.config/../../file) \"file\"\n").is_err());
+        assert!(Groups::from_text("(alias=./file) \"file\"\n").is_err());
+        assert!(Groups::from_text("(alias=.config/./file) \"file\"\n").is_err());
+        assert!(Groups::from_text("(alias=..file/.x) \"file\"\n").is_ok());
+        assert!(Groups::from_text("(alias=.file)\n- [editor]\n").is_err());
+    }
+
+    #[test]
+    fn hidden_prefix() {
+        let text = "(hidden=dot_)\n- [zsh]\n\"dot_zshrc\"\n\"dot_config\": [\"app\": [\"dot_rc\"]]\n\"dot_\"\n\n- [vim]\n\"dot_vimrc\"\n";
+        let groups = Groups::from_text(text).unwrap();
+        assert_eq!(groups.info.hidden_prefixes["zsh"], "dot_");
+        assert!(!groups.info.hidden_prefixes.contains_key("vim"));
+
+        let target_path = |file: &FileTree| file.extra().as_ref().unwrap().target_path.clone();
+        let targets: Vec<_> = groups.map["zsh"]
+            .iter()
+            .flat_map(|tree| tree.files().skip_dirs(true))
+            .map(target_path)
+            .collect();
+        let expected = [Some(".zshrc"), Some(".config/app/.rc"), None];
+        assert_eq!(targets, expected.map(|path| path.map(PathBuf::from)));
+        assert_eq!(target_path(&groups.map["vim"][0]), None);
+
+        assert!(Groups::from_text("(hidden=)\n- [zsh]\n").is_err());
+        assert!(Groups::from_text("(hidden=a/b)\n- [zsh]\n").is_err());
+        assert!(Groups::from_text("- [zsh]\n(hidden=dot_) \"dot_zshrc\"\n").is_err());
+    }
+
+    #[test]
+    fn lines_of_declarations() {
+        let text = "// comment\n- [i3]\n\".config\": [\n    \"i3\": [\"config\"]\n]\n\"link\" > \"config\"\n";
+        let lines = declaration_lines(text);
+        let line = |path: &str| lines.get(&("i3".to_string(), PathBuf::from(path))).copied();
+
+        assert_eq!(line(".config"), Some(3));
+        assert_eq!(line(".config/i3/config"), Some(4));
+        assert_eq!(line("link"), Some(6));
+        assert_eq!(line("config"), None);
+    }
 }
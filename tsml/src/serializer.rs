@@ -1,19 +1,31 @@
-use crate::{FileTree, Groups, Tags, TsmlError, TsmlResult};
+use crate::{
+    tags::{ALIAS_PREFIX, HIDDEN_PREFIX, MODE_PREFIX},
+    FileTree, Groups, Tags, TsmlError, TsmlResult,
+};
 
 // TODO: preserve comments, somehow
-// TODO: fix order
 // TODO: preserve order of values (iter.rs)
 
 // Where T is whatever it wants to be
 pub fn groups_to_tsml(groups: &Groups) -> TsmlResult<String> {
     let mut text = String::new();
-    for (i, group) in groups.map.iter().enumerate() {
+    for (i, key) in groups.ordered_names().into_iter().enumerate() {
         // Group separator
         if i != 0 {
             text.push('\n');
         }
-        let (key, files) = group;
-        add_group_to_tsml(&mut text, key, files)?;
+        let files = &groups.map[key];
+        // Group tags are also stored in each entry, in case they're missing from `info`
+        let group_tags = groups.info.group_tags.get(key).cloned().or_else(|| {
+            files.iter().find_map(|file| file.extra().as_ref()).map(|tags| tags.group_tags.clone())
+        });
+        let hidden_prefix = groups.info.hidden_prefixes.get(key);
+        let header_tags = group_tags
+            .unwrap_or_default()
+            .into_iter()
+            .chain(hidden_prefix.map(|prefix| format!("{}{}", HIDDEN_PREFIX, prefix)))
+            .collect();
+        add_group_to_tsml(&mut text, key, header_tags, files)?;
     }
     Ok(text)
 }
@@ -26,24 +38,42 @@ fn indent(text: &mut String, levels: usize) {
 
 fn add_tags(text: &mut String, tags: &Option<Tags>) {
     if let Some(tags) = tags {
-        if !tags.direct_tags.is_empty() {
-            // TODO: preserve order of the tags
-            text.push_str(format!("({}", tags.direct_tags.iter().next().unwrap()).as_str());
-            for tag in tags.direct_tags.iter().skip(1) {
-                text.push_str(format!(", {}", tag).as_str());
-            }
-            text.push_str(") ");
+        // Aliases and then the mode go last, inside of the same list
+        let aliases: Vec<String> = tags
+            .aliases
+            .iter()
+            .map(|alias| format!("{}{}", ALIAS_PREFIX, alias.to_string_lossy()))
+            .collect();
+        let mode = tags.mode.map(|mode| format!("{}{:04o}", MODE_PREFIX, mode));
+        if !tags.direct_tags.is_empty() || !aliases.is_empty() || mode.is_some() {
+            add_tag_list(text, tags.direct_tags.iter().chain(&aliases).chain(&mode));
+            text.push(' ');
         }
     } // end of adding tags
 }
 
+// TODO: preserve order of the tags
+fn add_tag_list<'a>(text: &mut String, tags: impl IntoIterator<Item = &'a String>) {
+    let tags: Vec<&str> = tags.into_iter().map(String::as_str).collect();
+    text.push_str(format!("({})", tags.join(", ")).as_str());
+}
+
 fn close_bracket(text: &mut String, at_indent_level: usize) {
     indent(text, at_indent_level);
     text.push_str("]\n");
 }
 
-// Todo: think about what to do here to deal with group tags
-fn add_group_to_tsml(text: &mut String, key: &str, files: &[FileTree]) -> TsmlResult<()> {
+fn add_group_to_tsml(
+    text: &mut String,
+    key: &str,
+    // The hidden prefix goes last
+    header_tags: Vec<String>,
+    files: &[FileTree],
+) -> TsmlResult<()> {
+    if !header_tags.is_empty() && key != "main" {
+        add_tag_list(text, &header_tags);
+        text.push('\n');
+    }
     if key != "main" {
         text.push_str(format!("- [{}]\n", key.trim_end_matches('/')).as_str());
     }
@@ -78,10 +108,9 @@ fn add_group_to_tsml(text: &mut String, key: &str, files: &[FileTree]) -> TsmlRe
                     text.push_str(": [");
                 },
                 FileTree::Symlink { target_path, .. } => {
-                    let target_path = target_path.file_name().ok_or(TsmlError::PathWithoutName)?;
                     let target_path = target_path
                         .to_str()
-                        .ok_or_else(|| TsmlError::NonUtf8Path(target_path.into()))?;
+                        .ok_or_else(|| TsmlError::NonUtf8Path(target_path.clone()))?;
                     text.push_str(&format!(" > \"{}\"", target_path));
                 },
             }
@@ -97,17 +126,52 @@ fn add_group_to_tsml(text: &mut String, key: &str, files: &[FileTree]) -> TsmlRe
     Ok(())
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use crate::tags;
-
-//     #[test]
-//     fn asd() {
-//         let file = File::new("asd", FileType::<tags>::Regular);
-//         for a in file.files() {
-//             println!("{:?}", a);
-//         }
-//         panic!();
-//     }
-// }
+#[cfg(test)]
+mod serializer_tests {
+    use crate::{groups_to_tsml, Groups};
+
+    #[test]
+    fn keeps_order_and_group_tags() {
+        let text =
+            "- [zsh]\n(mode=0600) \"zshrc\"\n\n(laptop, linux)\n- [i3]\n(work) \"config\"\n\n- \
+                    [alacritty]\n";
+        let mut groups = Groups::from_text(text).unwrap();
+        groups.map.remove("main");
+        assert_eq!(groups_to_tsml(&groups).unwrap(), text);
+
+        // Renamed groups keep their position and tags, new groups come last
+        assert!(groups.rename_group("i3", "sway"));
+        groups.map.insert("bash".to_string(), vec![]);
+        let expected =
+            "- [zsh]\n(mode=0600) \"zshrc\"\n\n(laptop, linux)\n- [sway]\n(work) \"config\"\n\n- \
+                        [alacritty]\n\n- [bash]\n";
+        assert_eq!(groups_to_tsml(&groups).unwrap(), expected);
+    }
+
+    #[test]
+    fn symlink_targets_are_kept_whole() {
+        let text = "- [vim]\n\".vimrc\" > \"../nvim/init.vim\"\n\"colors\" > \"/usr/share/vim\"\n";
+        let mut groups = Groups::from_text(text).unwrap();
+        groups.map.remove("main");
+        assert_eq!(groups_to_tsml(&groups).unwrap(), text);
+    }
+
+    #[test]
+    fn aliases_go_before_the_mode() {
+        let text = "- [shell]\n(work, alias=.config/fish/aliases, mode=0600) \"aliases\"\n";
+        let mut groups = Groups::from_text(text).unwrap();
+        groups.map.remove("main");
+        assert_eq!(groups_to_tsml(&groups).unwrap(), text);
+    }
+
+    #[test]
+    fn hidden_prefix_goes_after_the_group_tags() {
+        let text = "(linux, hidden=dot_)\n- [zsh]\n\"dot_zshrc\"\n\n(hidden=dot-)\n- [vim]\n\"dot-vimrc\"\n";
+        let mut groups = Groups::from_text(text).unwrap();
+        groups.map.remove("main");
+        assert_eq!(groups_to_tsml(&groups).unwrap(), text);
+
+        assert!(groups.rename_group("zsh", "shell"));
+        assert!(groups_to_tsml(&groups).unwrap().starts_with("(linux, hidden=dot_)\n- [shell]\n"));
+    }
+}
@@ -1,10 +1,54 @@
-use std::collections::BTreeSet;
+use std::{
+    collections::BTreeSet,
+    path::{Component, Path, PathBuf},
+};
+
+use permissions::PermissionBits;
+
+// Written inside of the tag list, as in `(work, mode=0600)`
+pub(crate) const MODE_PREFIX: &str = "mode=";
+
+// Also inside of the tag list, can be repeated, as in `(alias=.vim/.editorconfig)`
+pub(crate) const ALIAS_PREFIX: &str = "alias=";
+
+// Only above group headers, as in `(hidden=dot_)`
+pub(crate) const HIDDEN_PREFIX: &str = "hidden=";
 
 #[derive(Debug, Default, Hash, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Tags {
     pub direct_tags: BTreeSet<String>,
     pub group_tags: BTreeSet<String>,
     pub ancestor_tags: BTreeSet<String>,
+    /// Desired mode of the entry, it's not a tag, so it isn't inherited.
+    pub mode: Option<PermissionBits>,
+    /// Other paths where the entry is linked, relative to the target root like the entry, they
+    /// aren't tags either.
+    pub aliases: Vec<PathBuf>,
+    /// Path of the link relative to the target root, if the hidden prefix of the group makes it
+    /// differ from the path of the entry, filled by the parser.
+    pub target_path: Option<PathBuf>,
+}
+
+/// Parse an octal mode like `0600` or `600`, only the permission bits are accepted.
+pub fn parse_mode(text: &str) -> Option<PermissionBits> {
+    let bits = i32::from_str_radix(text, 8).ok()?;
+    PermissionBits::from_bits_checked(bits)
+}
+
+/// `path` with a leading dot instead of `prefix` in each of its names, as in `dot_config/dot_app`
+/// becoming `.config/.app` with `dot_`, names made of the prefix alone are kept.
+pub fn apply_hidden_prefix(path: &Path, prefix: &str) -> PathBuf {
+    path.components()
+        .map(|component| match component {
+            Component::Normal(name) => {
+                match name.to_str().and_then(|name| name.strip_prefix(prefix)) {
+                    Some(rest) if !rest.is_empty() => format!(".{}", rest).into(),
+                    _ => name.to_os_string(),
+                }
+            },
+            other => other.as_os_str().to_os_string(),
+        })
+        .collect()
 }
 
 impl Tags {
@@ -24,6 +68,11 @@ impl Tags {
         self.ancestor_tags.insert(name.as_ref().to_string())
     }
 
+    /// Every tag that applies to the entry: direct, group and ancestor tags.
+    pub fn effective_tags(&self) -> BTreeSet<&String> {
+        self.direct_tags.iter().chain(&self.group_tags).chain(&self.ancestor_tags).collect()
+    }
+
     pub fn inherit_from(&mut self, parent: &Self) {
         // Set `a` receives all elements from set `b`
         let merge_sets = |a: &mut BTreeSet<String>, b: &BTreeSet<String>| {
@@ -0,0 +1,138 @@
+//! Versions of the syntax, picked by a marker in the first line of the file.
+//!
+//! Files without the marker are from version 1, where comments start with `//`, since version 2
+//! they start with `#`, and the marker is `# tsml-version: 2`.
+
+use crate::{
+    lexer::{lex_with_comments, LexToken},
+    Groups, TsmlError, TsmlResult,
+};
+
+/// Version of the syntax written by this crate.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Version of the files without a marker.
+pub const UNVERSIONED: u32 = 1;
+
+const MARKER_KEY: &str = "tsml-version:";
+
+/// What starts a comment in `version`.
+pub fn comment_prefix(version: u32) -> &'static str {
+    if version == UNVERSIONED {
+        "//"
+    } else {
+        "#"
+    }
+}
+
+/// The first line of a file of `version`, without the line break.
+pub fn version_marker(version: u32) -> String {
+    format!("{} {} {}", comment_prefix(version), MARKER_KEY, version)
+}
+
+/// Version of the syntax of `text`, from the marker in its first line, [`UNVERSIONED`] if missing.
+///
+/// Fails with [`TsmlError::UnsupportedVersion`] for versions newer than [`CURRENT_VERSION`].
+pub fn detect_version(text: &str) -> TsmlResult<u32> {
+    let first_line = text.lines().next().unwrap_or_default().trim();
+    let value = ["#", "//"]
+        .iter()
+        .filter_map(|prefix| first_line.strip_prefix(prefix))
+        .find_map(|rest| rest.trim_start().strip_prefix(MARKER_KEY));
+
+    match value.map(str::trim) {
+        None => Ok(UNVERSIONED),
+        Some(value) => match value.parse() {
+            Ok(version) if (UNVERSIONED..=CURRENT_VERSION).contains(&version) => Ok(version),
+            _ => Err(TsmlError::UnsupportedVersion(value.to_string())),
+        },
+    }
+}
+
+/// Rewrite `text` to the syntax of [`CURRENT_VERSION`], adding the marker, comments are kept, and
+/// everything else is left as it was.
+///
+/// Returns `text` unchanged if it's already at the current version, fails if it doesn't parse.
+pub fn migrate(text: &str) -> TsmlResult<String> {
+    let version = detect_version(text)?;
+    // Don't rewrite what we can't read
+    Groups::from_text(text)?;
+    if version == CURRENT_VERSION {
+        return Ok(text.to_string());
+    }
+
+    let old_prefix = comment_prefix(version);
+    let new_prefix = comment_prefix(CURRENT_VERSION);
+
+    let mut migrated = String::with_capacity(text.len());
+    let mut copied_until = 0;
+    for (token, range) in lex_with_comments(text) {
+        if token == LexToken::SlashComment {
+            migrated.push_str(&text[copied_until..range.start]);
+            migrated.push_str(new_prefix);
+            migrated.push_str(&text[range.start + old_prefix.len()..range.end]);
+            copied_until = range.end;
+        }
+    }
+    migrated.push_str(&text[copied_until..]);
+
+    // The old marker, if any, is replaced by the new one
+    let body = if text.lines().next().unwrap_or_default().contains(MARKER_KEY) {
+        migrated.split_once('\n').map(|(_, rest)| rest).unwrap_or_default()
+    } else {
+        &migrated
+    };
+    Ok(format!("{}\n{}", version_marker(CURRENT_VERSION), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_detected() {
+        assert_eq!(detect_version("- [zsh]\n").unwrap(), UNVERSIONED);
+        assert_eq!(detect_version("").unwrap(), UNVERSIONED);
+        assert_eq!(detect_version("// tsml-version: 1\n").unwrap(), 1);
+        assert_eq!(detect_version("# tsml-version: 2\n- [zsh]\n").unwrap(), 2);
+        assert_eq!(detect_version("#tsml-version:2").unwrap(), 2);
+        // Only the first line counts
+        assert_eq!(detect_version("\n# tsml-version: 2\n").unwrap(), UNVERSIONED);
+
+        let newer = detect_version("# tsml-version: 3\n");
+        assert!(matches!(newer, Err(TsmlError::UnsupportedVersion(version)) if version == "3"));
+        assert!(detect_version("# tsml-version: two\n").is_err());
+    }
+
+    #[test]
+    fn comments_follow_the_version() {
+        let v1 = "// zsh stuff\n- [zsh]\n\".zshrc\" // the rc\n";
+        let v2 = "# tsml-version: 2\n# zsh stuff\n- [zsh]\n\".zshrc\" # the rc\n";
+        assert_eq!(Groups::from_text(v1).unwrap().info.version, 1);
+        assert_eq!(Groups::from_text(v2).unwrap().info.version, 2);
+
+        // Mixing them up is an error, instead of being silently misread
+        assert!(Groups::from_text("# zsh stuff\n- [zsh]\n").is_err());
+        assert!(Groups::from_text("# tsml-version: 2\n// zsh stuff\n- [zsh]\n").is_err());
+    }
+
+    #[test]
+    fn migration_keeps_comments_and_layout() {
+        let v1 = "// Header\n//\n// - [example]\n\n- [zsh]\n\".zshrc\" // the rc\n\"a//b\"\n";
+        let expected =
+            "# tsml-version: 2\n# Header\n#\n# - [example]\n\n- [zsh]\n\".zshrc\" # the rc\n\"a//b\"\n";
+        let migrated = migrate(v1).unwrap();
+        assert_eq!(migrated, expected);
+        assert_eq!(migrate(&migrated).unwrap(), migrated);
+
+        let groups = Groups::from_text(&migrated).unwrap();
+        assert_eq!(groups.info.file_header, "# tsml-version: 2\n# Header\n#\n# - [example]\n\n");
+        assert_eq!(groups.map["zsh"].len(), 2);
+
+        // An explicit old marker is replaced
+        let migrated = migrate("// tsml-version: 1\n- [zsh]\n").unwrap();
+        assert_eq!(migrated, "# tsml-version: 2\n- [zsh]\n");
+
+        assert!(migrate("- [zsh]\n\"unclosed\": [\n").is_err());
+    }
+}